@@ -1,7 +1,7 @@
 #![allow(clippy::bool_assert_comparison)]
 
-use cargo_edit::CargoResult;
-use clap::Args;
+use cargo_edit::{write_completions, CargoResult, Shell};
+use clap::{Args, CommandFactory};
 
 /// Add dependencies to a Cargo.toml manifest file.
 #[derive(Debug, Args)]
@@ -98,6 +98,14 @@ pub struct AddArgs {
     #[arg(long)]
     pub offline: bool,
 
+    /// Require `Cargo.lock` to be up to date
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Require `Cargo.lock` to be up to date, without accessing the network
+    #[arg(long)]
+    pub frozen: bool,
+
     /// Don't actually write the manifest
     #[arg(long)]
     pub dry_run: bool,
@@ -143,10 +151,18 @@ pub struct AddArgs {
         group = "git-ref"
     )]
     pub rev: Option<String>,
+
+    /// Print the completion script for `SHELL` and exit
+    #[arg(long, value_name = "SHELL", exclusive = true, hide = true)]
+    pub generate_completions: Option<Shell>,
 }
 
 impl AddArgs {
     pub fn exec(self) -> CargoResult<()> {
+        if let Some(shell) = self.generate_completions {
+            let cmd = crate::cli::Command::command();
+            return write_completions(shell, cmd, &mut std::io::stdout().lock());
+        }
         anyhow::bail!(
             "`cargo add` has been merged into cargo 1.62+ as of cargo-edit 0.10, either
 - Upgrade cargo, like with `rustup update`
@@ -13,6 +13,12 @@ impl Command {
             Self::Rm(add) => add.exec(),
         }
     }
+
+    pub fn reports_json(&self) -> bool {
+        match self {
+            Self::Rm(add) => add.reports_json(),
+        }
+    }
 }
 
 #[test]
@@ -0,0 +1,54 @@
+//! Describing removed dependencies for `--message-format json`.
+use std::path::Path;
+
+use cargo_edit::{Dependency, Source};
+
+/// `dep` in the table at `section` as structured data
+pub fn to_json(
+    dep: &Dependency,
+    section: &[String],
+    manifest_path: &Path,
+    member: Option<&str>,
+    restore: Option<&str>,
+) -> serde_json::Value {
+    let (table, target) = if section.len() >= 3 {
+        (section[2].clone(), Some(section[1].as_str()))
+    } else {
+        (section.join("."), None)
+    };
+    let source = match dep.source() {
+        Some(Source::Registry(_)) => serde_json::json!({
+            "kind": "registry",
+            "registry": dep.registry(),
+        }),
+        Some(Source::Path(src)) => serde_json::json!({
+            "kind": "path",
+            "path": src.path,
+        }),
+        Some(Source::Git(src)) => serde_json::json!({
+            "kind": "git",
+            "git": src.git,
+            "branch": src.branch,
+            "tag": src.tag,
+            "rev": src.rev,
+        }),
+        Some(Source::Workspace(_)) | None => serde_json::json!({
+            "kind": "workspace",
+        }),
+    };
+    serde_json::json!({
+        "reason": "removed-dependency",
+        "manifest_path": manifest_path,
+        "member": member,
+        "table": table,
+        "target": target,
+        "name": dep.toml_key(),
+        "package": dep.name,
+        "version": dep.version(),
+        "features": dep.features.clone().unwrap_or_default(),
+        "default_features": dep.default_features().unwrap_or(true),
+        "optional": dep.optional().unwrap_or(false),
+        "source": source,
+        "restore": restore,
+    })
+}
@@ -12,18 +12,41 @@
 )]
 
 mod cli;
+mod json;
 mod rm;
 
 use std::process;
 
+use cargo_edit::ops::LockfileUpdateFailed;
+use cargo_edit::ErrorKind;
 use clap::Parser;
 
+/// Exit code for when the manifests were written but `Cargo.lock` couldn't be updated
+const UPDATE_FAILED_EXIT_CODE: i32 = 3;
+
 fn main() {
     let args = cli::Command::parse();
+    let json = args.reports_json();
 
     if let Err(err) = args.exec() {
         eprintln!("Error: {err:?}");
 
-        process::exit(1);
+        let kind = ErrorKind::of(&err);
+        let code = if err.downcast_ref::<LockfileUpdateFailed>().is_some() {
+            UPDATE_FAILED_EXIT_CODE
+        } else {
+            kind.exit_code()
+        };
+        if json {
+            let message = serde_json::json!({
+                "reason": "error",
+                "kind": kind.as_str(),
+                "transient": kind.is_transient(),
+                "exit_code": code,
+                "message": format!("{err:#}"),
+            });
+            println!("{message}");
+        }
+        process::exit(code);
     }
 }
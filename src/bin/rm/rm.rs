@@ -1,28 +1,31 @@
-use cargo_edit::shell_status;
-use cargo_edit::shell_warn;
-use cargo_edit::CargoResult;
-use cargo_edit::{manifest_from_pkgid, LocalManifest};
-use clap::Args;
-use std::borrow::Cow;
 use std::path::PathBuf;
 
+use cargo_edit::ops::{self, RmChange, RmChangeKind, RmEvent, RmOptions};
+use cargo_edit::{
+    init_tracing, set_color, shell_note, shell_status, shell_warn, write_completions, CargoResult,
+    ColorWhen, Dependency, FeatureChange, Shell,
+};
+use clap::{Args, CommandFactory};
+
+use crate::json;
+
 /// Remove a dependency from a Cargo.toml manifest file.
 #[derive(Debug, Args)]
 #[command(version)]
 pub struct RmArgs {
     /// Dependencies to be removed
-    #[arg(value_name = "DEP_ID", required = true)]
+    #[arg(value_name = "DEP_ID", required_unless_present = "unused")]
     crates: Vec<String>,
 
-    /// Remove as development dependency
+    /// Only remove from development dependencies
     #[arg(long, short = 'D', conflicts_with = "build", help_heading = "Section")]
     dev: bool,
 
-    /// Remove as build dependency
+    /// Only remove from build dependencies
     #[arg(long, short = 'B', conflicts_with = "dev", help_heading = "Section")]
     build: bool,
 
-    /// Remove as dependency from the given target platform
+    /// Only remove from dependencies for the given target platform
     #[arg(long, value_parser = clap::builder::NonEmptyStringValueParser::new(), help_heading = "Section")]
     target: Option<String>,
 
@@ -31,13 +34,107 @@ pub struct RmArgs {
     manifest_path: Option<PathBuf>,
 
     /// Package to remove from
-    #[arg(long = "package", short = 'p', value_name = "PKGID")]
+    #[arg(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace"
+    )]
     pkgid: Option<String>,
 
+    /// Remove from all packages in the workspace
+    #[arg(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Packages to leave untouched with `--workspace`
+    #[arg(long, requires = "workspace")]
+    exclude: Vec<String>,
+
+    /// Remove these features from the dependency instead of the dependency
+    #[arg(short = 'F', long, value_name = "FEATURES", value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Remove the dependencies that `cargo check` reports as unused
+    #[arg(long, conflicts_with_all = ["crates", "features", "workspace"])]
+    unused: bool,
+
+    /// Also remove the dependency from members inheriting it from `[workspace.dependencies]`
+    #[arg(long)]
+    recursive: bool,
+
+    /// Remove every renamed dependency on the package, if there are several
+    #[arg(long)]
+    all_matching: bool,
+
+    /// Keep `[patch]` and `[replace]` entries for dependencies that are no longer used
+    #[arg(long)]
+    keep_patches: bool,
+
+    /// Remove `[patch]` and `[replace]` entries for dependencies that are no longer used (default)
+    #[arg(long, overrides_with = "keep_patches", hide = true)]
+    prune_patches: bool,
+
+    /// Remove optional dependencies even if features enable them, editing those features
+    #[arg(long)]
+    force: bool,
+
+    /// Look for uses of the dependency in the package's source before removing it
+    #[arg(
+        long,
+        num_args=0..=1,
+        value_name = "warn|error",
+        hide_possible_values = true,
+        default_missing_value = "warn",
+        value_enum
+    )]
+    check_usage: Option<CheckUsage>,
+
+    /// How to report removed dependencies, `json` prints one object per line to stdout
+    #[arg(
+        long,
+        value_name = "human|json",
+        hide_possible_values = true,
+        default_value = "human",
+        value_enum
+    )]
+    message_format: MessageFormat,
+
+    /// Run without accessing the network
+    #[arg(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[arg(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` to be up to date, without accessing the network
+    #[arg(long)]
+    frozen: bool,
+
+    /// Use verbose output (-vv and -vvv trace decisions)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Ignore cargo-edit's workspace and user configuration
+    #[arg(long)]
+    no_config: bool,
+
+    /// Coloring: auto, always, never
+    #[arg(long, value_name = "WHEN", global = true)]
+    color: Option<ColorWhen>,
+
     /// Unstable (nightly-only) flags
     #[arg(short = 'Z', value_name = "FLAG", global = true, value_enum)]
     unstable_features: Vec<UnstableOptions>,
 
+    /// Don't update `Cargo.lock` afterwards, a failed update exits with status 3
+    #[arg(long)]
+    no_update_lockfile: bool,
+
+    /// Update `Cargo.lock` afterwards, even if turned off by the configuration
+    #[arg(long, overrides_with = "no_update_lockfile", hide = true)]
+    update_lockfile: bool,
+
     /// Don't actually write the manifest
     #[arg(long)]
     dry_run: bool,
@@ -45,74 +142,286 @@ pub struct RmArgs {
     /// Do not print any output in case of success
     #[arg(long, short)]
     quiet: bool,
+
+    /// Print the completion script for `SHELL` and exit
+    #[arg(long, value_name = "SHELL", exclusive = true, hide = true)]
+    generate_completions: Option<Shell>,
 }
 
 impl RmArgs {
     pub fn exec(&self) -> CargoResult<()> {
+        if let Some(shell) = self.generate_completions {
+            let cmd = crate::cli::Command::command();
+            return write_completions(shell, cmd, &mut std::io::stdout().lock());
+        }
         exec(self)
     }
 
-    /// Get dependency section
-    pub fn get_section(&self) -> Vec<String> {
-        let section_name = if self.dev {
-            "dev-dependencies"
-        } else if self.build {
-            "build-dependencies"
-        } else {
-            "dependencies"
-        };
-
-        if let Some(ref target) = self.target {
-            assert!(!target.is_empty(), "Target specification may not be empty");
-
-            vec!["target".to_owned(), target.clone(), section_name.to_owned()]
-        } else {
-            vec![section_name.to_owned()]
+    /// Whether messages, including errors, are reported as JSON
+    pub fn reports_json(&self) -> bool {
+        self.message_format == MessageFormat::Json
+    }
+
+    fn options(&self) -> RmOptions {
+        RmOptions {
+            dependencies: self.crates.clone(),
+            manifest_path: self.manifest_path.clone(),
+            package: self.pkgid.clone(),
+            workspace: self.workspace,
+            exclude: self.exclude.clone(),
+            dev: self.dev,
+            build: self.build,
+            target: self.target.clone(),
+            features: self.features.clone(),
+            unused: self.unused,
+            recursive: self.recursive,
+            all_matching: self.all_matching,
+            keep_patches: self.keep_patches && !self.prune_patches,
+            force: self.force,
+            check_usage: self.check_usage.map(CheckUsage::to_ops),
+            update_lockfile: if self.no_update_lockfile {
+                Some(false)
+            } else if self.update_lockfile {
+                Some(true)
+            } else {
+                None
+            },
+            no_config: self.no_config,
+            dry_run: self.dry_run,
+            offline: self.offline || self.frozen,
+            locked: self.locked || self.frozen,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum CheckUsage {
+    // Report uses, but remove the dependency anyway
+    Warn,
+    // Don't remove a dependency that is still used
+    Error,
+}
+
+impl CheckUsage {
+    fn to_ops(self) -> ops::CheckUsage {
+        match self {
+            Self::Warn => ops::CheckUsage::Warn,
+            Self::Error => ops::CheckUsage::Error,
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
 enum UnstableOptions {}
 
 fn exec(args: &RmArgs) -> CargoResult<()> {
-    let manifest_path = if let Some(ref pkgid) = args.pkgid {
-        let pkg = manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?;
-        Cow::Owned(Some(pkg.manifest_path.into_std_path_buf()))
-    } else {
-        Cow::Borrowed(&args.manifest_path)
-    };
-    let mut manifest = LocalManifest::find(manifest_path.as_deref())?;
-    let deps = &args.crates;
+    set_color(args.color)?;
+    init_tracing(args.verbose)?;
+    if args.unused && !args.quiet {
+        shell_warn(
+            "`--unused` relies on the `unused_crate_dependencies` lint, which misses uses through macros; review the list with `--dry-run` before removing",
+        )?;
+    }
+
+    let report = ops::rm_with(&args.options(), |event| report_event(args, event))?;
 
-    deps.iter()
-        .map(|dep| {
+    if args.unused && report.changes.is_empty() {
+        if !args.quiet {
+            shell_note("no unused dependencies found")?;
+        }
+    } else if args.dry_run {
+        shell_warn("aborting rm due to dry run")?;
+    }
+
+    Ok(())
+}
+
+fn report_event(args: &RmArgs, event: &RmEvent) -> CargoResult<()> {
+    match event {
+        RmEvent::TargetCfgUnavailable { target, error } => {
+            shell_warn(&format!(
+                "only `[target.{target}]` tables are matched, `cfg(...)` tables could not be evaluated: {error}"
+            ))?;
+        }
+        RmEvent::Changed(change) => report_change(args, change)?,
+        RmEvent::StillUsed { key, path, line } => {
+            shell_warn(&format!(
+                "{key} appears to still be used at {}:{line}",
+                path.display()
+            ))?;
+        }
+        RmEvent::FeatureNarrowed {
+            member,
+            feature,
+            lost,
+        } => {
+            if !args.quiet {
+                let lost = lost
+                    .iter()
+                    .map(|activation| format!("`{activation}`"))
+                    .collect::<Vec<_>>();
+                let owner = member
+                    .as_ref()
+                    .map(|owner| format!(" of `{owner}`"))
+                    .unwrap_or_default();
+                shell_note(&format!(
+                    "feature `{feature}`{owner} no longer enables {}",
+                    lost.join(", ")
+                ))?;
+            }
+        }
+        RmEvent::NotADependency { name, member } => {
+            if !args.quiet {
+                shell_note(&format!("`{name}` is not a dependency of `{member}`"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn report_change(args: &RmArgs, change: &RmChange) -> CargoResult<()> {
+    let owner = change.member.as_deref();
+    match &change.kind {
+        RmChangeKind::Dependency {
+            table,
+            key,
+            name,
+            inherited,
+            entry,
+            restore,
+        } => {
             if !args.quiet {
-                let section = args.get_section();
-                let section = if section.len() >= 3 {
-                    format!("{} for target `{}`", &section[2], &section[1])
+                let label = if *inherited || key == name {
+                    key.clone()
                 } else {
-                    section[0].clone()
+                    format!("{key} (package `{name}`)")
                 };
-                shell_status("Removing", &format!("{dep} from {section}",))?;
+                shell_status(
+                    "Removing",
+                    &format!("{label} from {}", section_location(table, owner)),
+                )?;
             }
-            let result = manifest
-                .remove_from_table(&args.get_section(), dep)
-                .map_err(Into::into);
-
-            // Now that we have removed the crate, if that was the last reference to that crate,
-            // then we need to drop any explicitly activated features on that crate.
-            manifest.gc_dep(dep);
-
-            result
-        })
-        .collect::<CargoResult<Vec<_>>>()?;
+            if let Some(dependency) = entry {
+                report_removal(args, change, table, dependency, restore.as_deref())?;
+            }
+        }
+        RmChangeKind::DependencyFeature {
+            table,
+            key,
+            feature,
+        } => {
+            if !args.quiet {
+                shell_status(
+                    "Removing",
+                    &format!(
+                        "feature `{feature}` from {key} in {}",
+                        section_location(table, owner)
+                    ),
+                )?;
+            }
+        }
+        RmChangeKind::Features(change) => report_feature_change(change, args.quiet)?,
+        RmChangeKind::Patch { table, key } => {
+            if !args.quiet {
+                shell_status("Removing", &format!("{key} from {}", table.join(".")))?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    if args.dry_run {
-        shell_warn("aborting rm due to dry run")?;
+/// Describe the removed entry for `dependency` in the table at `section`
+///
+/// Humans get told how to add it back, `--message-format json` prints the whole entry.
+fn report_removal(
+    args: &RmArgs,
+    change: &RmChange,
+    section: &[String],
+    dependency: &Dependency,
+    restore: Option<&str>,
+) -> CargoResult<()> {
+    if args.reports_json() {
+        let message = json::to_json(
+            dependency,
+            section,
+            &change.manifest_path,
+            change.member.as_deref(),
+            restore,
+        );
+        println!("{message}");
+    } else if args.quiet {
+        return Ok(());
+    } else if let Some(restore) = restore {
+        shell_note(&format!("restore with `{restore}`"))?;
     } else {
-        manifest.write()?;
+        let crate_root = change
+            .manifest_path
+            .parent()
+            .expect("manifest path is absolute");
+        let item = dependency.to_toml(crate_root);
+        shell_note(&format!(
+            "restore with `{} = {}` in `[workspace.dependencies]`",
+            dependency.toml_key(),
+            item.to_string().trim()
+        ))?;
     }
+    Ok(())
+}
 
+fn report_feature_change(change: &FeatureChange, quiet: bool) -> CargoResult<()> {
+    match change {
+        FeatureChange::RemovedActivation {
+            feature,
+            activation,
+        } => {
+            if !quiet {
+                shell_status(
+                    "Removing",
+                    &format!("`{activation}` from feature `{feature}`"),
+                )?;
+            }
+        }
+        FeatureChange::RemovedFeature { feature } => {
+            if !quiet {
+                shell_status("Removing", &format!("feature `{feature}`"))?;
+            }
+        }
+        FeatureChange::KeptEmptyFeature {
+            feature,
+            activated_by,
+        } => {
+            let activated_by = activated_by
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            shell_warn(&format!(
+                "feature `{feature}` is now empty but is kept as it is activated by {activated_by}"
+            ))?;
+        }
+    }
     Ok(())
 }
+
+/// Human-readable name of a dependency table, along with the member it belongs to
+fn section_location(section: &[String], owner: Option<&str>) -> String {
+    match owner {
+        Some(owner) => format!("{} of `{owner}`", section_name(section)),
+        None => section_name(section),
+    }
+}
+
+/// Human-readable name of a dependency table, e.g. ``dependencies for target `cfg(unix)` ``
+fn section_name(section: &[String]) -> String {
+    if section.len() >= 3 {
+        format!("{} for target `{}`", &section[2], &section[1])
+    } else {
+        section.join(".")
+    }
+}
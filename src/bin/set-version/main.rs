@@ -15,10 +15,10 @@
 mod cli;
 mod errors;
 mod set_version;
-mod version;
 
 use std::process;
 
+use cargo_edit::ErrorKind;
 use clap::Parser;
 
 fn main() {
@@ -27,6 +27,6 @@ fn main() {
     if let Err(err) = args.exec() {
         eprintln!("Error: {err:?}");
 
-        process::exit(1);
+        process::exit(ErrorKind::of(&err).exit_code());
     }
 }
@@ -16,6 +16,7 @@ mod upgrade;
 
 use std::process;
 
+use cargo_edit::ErrorKind;
 use clap::Parser;
 
 fn main() {
@@ -24,6 +25,6 @@ fn main() {
     if let Err(err) = args.exec() {
         eprintln!("Error: {err:?}");
 
-        process::exit(1);
+        process::exit(ErrorKind::of(&err).exit_code());
     }
 }
@@ -0,0 +1,357 @@
+//! Versions of crates looked up in remote registries, shared on disk by all commands.
+//!
+//! Each registry gets a directory of its own under the platform's cache directory, with an entry
+//! per crate laid out like the index.  Entries are replaced atomically, and the lock taken while
+//! writing one keeps concurrent commands from fetching and writing it at the same time.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use url::Url;
+
+use crate::local_source::index_file;
+use crate::manifest::create_temp;
+use crate::trace::redact_url;
+use crate::version_source::CrateVersion;
+
+/// How long versions that were looked up are used before asking the registry again
+const TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Time between attempts to take the lock on an entry
+const LOCK_BACKOFF: Duration = Duration::from_millis(20);
+
+/// Attempts to take the lock on an entry before giving up on caching it
+const LOCK_ATTEMPTS: u32 = 50;
+
+/// Age after which a lock is assumed to be left over from a command that died
+const STALE_LOCK: Duration = Duration::from_secs(30);
+
+/// When this command last updated the index of a registry, by the directory of its cache
+static INDEX_UPDATES: Mutex<BTreeMap<PathBuf, u64>> = Mutex::new(BTreeMap::new());
+
+/// Cached lookups of one registry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LookupCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl LookupCache {
+    /// The cache of `registry`, unless it is on the local filesystem and cheap to read anyway
+    pub(crate) fn for_registry(registry: &Url) -> Option<Self> {
+        let dir = Self::dir_of(registry)?;
+        debug!(
+            "caching lookups of {} in {}",
+            redact_url(registry),
+            dir.display()
+        );
+        Some(Self::new(dir, TTL))
+    }
+
+    pub(crate) fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// Stop using the lookups of `registry` made so far, as its index was just updated
+    pub(crate) fn invalidate(registry: &Url) {
+        if let Some(dir) = Self::dir_of(registry) {
+            Self::new(dir, TTL).invalidate_entries();
+        }
+    }
+
+    fn invalidate_entries(&self) {
+        trace!("invalidating the cache entries in {}", self.dir.display());
+        let mut updates = INDEX_UPDATES.lock().unwrap_or_else(|err| err.into_inner());
+        updates.insert(self.dir.clone(), now().as_secs());
+    }
+
+    fn dir_of(registry: &Url) -> Option<PathBuf> {
+        if registry.scheme() == "file" || registry.scheme().ends_with("+file") {
+            return None;
+        }
+        Some(cache_dir()?.join("lookups").join(registry_dir(registry)))
+    }
+
+    /// The versions of `name` looked up less than the TTL ago
+    pub(crate) fn get(&self, name: &str) -> Option<Vec<CrateVersion>> {
+        let path = self.entry_path(name)?;
+        let content = fs::read_to_string(&path).ok()?;
+        let entry = match serde_json::from_str::<CacheEntry>(&content) {
+            Ok(entry) => entry,
+            Err(err) => {
+                trace!("ignoring invalid cache entry {}: {err}", path.display());
+                return None;
+            }
+        };
+        let age = now().checked_sub(Duration::from_secs(entry.fetched))?;
+        if self.ttl <= age {
+            trace!("cache entry {} expired", path.display());
+            return None;
+        }
+        if self
+            .updated_at()
+            .map_or(false, |updated| entry.fetched <= updated)
+        {
+            trace!("cache entry {} predates the index update", path.display());
+            return None;
+        }
+        trace!("using cache entry {}", path.display());
+        entry
+            .versions
+            .into_iter()
+            .map(CachedVersion::parse)
+            .collect()
+    }
+
+    /// Remember the versions of `name`, best-effort
+    pub(crate) fn insert(&self, name: &str, versions: &[CrateVersion]) {
+        let path = match self.entry_path(name) {
+            Some(path) => path,
+            None => return,
+        };
+        let entry = CacheEntry {
+            fetched: now().as_secs(),
+            versions: versions.iter().map(CachedVersion::from).collect(),
+        };
+        if let Err(err) = write_entry(&path, &entry) {
+            trace!("not caching {}: {err}", path.display());
+        }
+    }
+
+    fn updated_at(&self) -> Option<u64> {
+        let updates = INDEX_UPDATES.lock().unwrap_or_else(|err| err.into_inner());
+        updates.get(&self.dir).copied()
+    }
+
+    fn entry_path(&self, name: &str) -> Option<PathBuf> {
+        let name = name.to_lowercase();
+        if name.is_empty() || !name.is_ascii() || name.contains(['/', '\\', '.']) {
+            return None;
+        }
+        let mut path = self.dir.join(index_file(&name));
+        path.set_file_name(format!("{name}.json"));
+        Some(path)
+    }
+}
+
+fn write_entry(path: &Path, entry: &CacheEntry) -> std::io::Result<()> {
+    let dir = path.parent().expect("entries are in a directory");
+    fs::create_dir_all(dir)?;
+    let _lock = match EntryLock::acquire(&path.with_extension("lock"))? {
+        Some(lock) => lock,
+        // Written by another command right now, which is as good
+        None => return Ok(()),
+    };
+
+    let contents = serde_json::to_vec(entry)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (temp_path, mut temp) = create_temp(dir, &file_name)?;
+    // Not synced, a torn entry after a crash is only a cache miss
+    let result = temp.write_all(&contents).and_then(|()| {
+        drop(temp);
+        fs::rename(&temp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Advisory lock on a cache entry, released when dropped
+#[derive(Debug)]
+struct EntryLock(PathBuf);
+
+impl EntryLock {
+    /// Take the lock at `path`, or `None` if it stayed taken
+    fn acquire(path: &Path) -> std::io::Result<Option<Self>> {
+        for _ in 0..LOCK_ATTEMPTS {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(_) => return Ok(Some(Self(path.to_owned()))),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .map_or(false, |age| STALE_LOCK < age);
+                    if stale {
+                        trace!("removing stale lock {}", path.display());
+                        let _ = fs::remove_file(path);
+                    } else {
+                        std::thread::sleep(LOCK_BACKOFF);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Directory of `registry`'s entries, readable but distinct for every index URL
+fn registry_dir(registry: &Url) -> String {
+    let host = registry.host_str().unwrap_or("registry");
+    format!("{host}-{:016x}", fnv1a(registry.as_str().as_bytes()))
+}
+
+/// A hash that stays the same across releases of Rust, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where cargo-edit keeps its cache, `CARGO_EDIT_CACHE_DIR` overriding the platform's default
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("CARGO_EDIT_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home::home_dir().map(|home| home.join("Library").join("Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home::home_dir().map(|home| home.join(".cache")))
+    };
+    Some(base?.join("cargo-edit"))
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch at which the versions were looked up
+    fetched: u64,
+    versions: Vec<CachedVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedVersion {
+    name: String,
+    version: String,
+    yanked: bool,
+    features: BTreeMap<String, Vec<String>>,
+    rust_version: Option<String>,
+}
+
+impl CachedVersion {
+    fn parse(self) -> Option<CrateVersion> {
+        Some(CrateVersion {
+            name: self.name,
+            version: self.version.parse().ok()?,
+            yanked: self.yanked,
+            available_features: self.features,
+            rust_version: self.rust_version,
+        })
+    }
+}
+
+impl From<&CrateVersion> for CachedVersion {
+    fn from(version: &CrateVersion) -> Self {
+        Self {
+            name: version.name.clone(),
+            version: version.version.to_string(),
+            yanked: version.yanked,
+            features: version.available_features.clone(),
+            rust_version: version.rust_version.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn crate_version(version: &str) -> CrateVersion {
+        CrateVersion {
+            name: "my-crate".to_owned(),
+            version: version.parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::from([("std".to_owned(), vec![])]),
+            rust_version: Some("1.60".to_owned()),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let cache = LookupCache::new(dir.path().to_owned(), TTL);
+        assert_eq!(cache.get("my-crate"), None);
+
+        let versions = vec![crate_version("1.0.0"), crate_version("1.1.0-alpha.1")];
+        cache.insert("my-crate", &versions);
+        assert_eq!(cache.get("my-crate"), Some(versions));
+        assert_eq!(cache.get("My-Crate").map(|v| v.len()), Some(2));
+        assert!(dir.path().join("my/-c/my-crate.json").is_file());
+        assert!(!dir.path().join("my/-c/my-crate.lock").exists());
+
+        cache.insert("missing", &[]);
+        assert_eq!(cache.get("missing"), Some(vec![]));
+    }
+
+    #[test]
+    fn expires() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let cache = LookupCache::new(dir.path().to_owned(), Duration::ZERO);
+        cache.insert("my-crate", &[crate_version("1.0.0")]);
+        assert_eq!(cache.get("my-crate"), None);
+    }
+
+    #[test]
+    fn invalidated_by_index_update() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let cache = LookupCache::new(dir.path().to_owned(), TTL);
+        cache.insert("my-crate", &[crate_version("1.0.0")]);
+        cache.invalidate_entries();
+        assert_eq!(cache.get("my-crate"), None);
+    }
+
+    #[test]
+    fn waits_for_lock() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let cache = LookupCache::new(dir.path().to_owned(), TTL);
+        let path = cache.entry_path("my-crate").unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let lock = EntryLock::acquire(&path.with_extension("lock"))
+            .unwrap()
+            .unwrap();
+
+        // Another command writing the entry is left to it
+        cache.insert("my-crate", &[crate_version("1.0.0")]);
+        assert_eq!(cache.get("my-crate"), None);
+
+        drop(lock);
+        cache.insert("my-crate", &[crate_version("1.0.0")]);
+        assert_eq!(cache.get("my-crate").map(|v| v.len()), Some(1));
+    }
+
+    #[test]
+    fn registries_kept_apart() {
+        let crates_io = Url::parse("https://github.com/rust-lang/crates.io-index").unwrap();
+        let private = Url::parse("https://github.com/example/index").unwrap();
+        assert_ne!(registry_dir(&crates_io), registry_dir(&private));
+        assert!(registry_dir(&crates_io).starts_with("github.com-"));
+
+        let local = Url::parse("file:///srv/registry").unwrap();
+        assert_eq!(LookupCache::for_registry(&local), None);
+    }
+}
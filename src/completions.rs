@@ -0,0 +1,57 @@
+//! Shell completions for the binaries.
+use std::io::Write;
+
+pub use clap_complete::Shell;
+
+use crate::CargoResult;
+
+/// Write the completion script of a binary for `shell` to `out`
+///
+/// `cmd` is the command of a binary like `cargo-upgrade`, which cargo runs as
+/// `cargo-upgrade upgrade`, and the script completes that.  Fish completions add up, so the script
+/// for fish also completes `cargo upgrade` without taking over the rest of cargo's completions.
+pub fn write_completions(shell: Shell, cmd: clap::Command, out: &mut dyn Write) -> CargoResult<()> {
+    let cmd = cmd.disable_help_subcommand(true);
+    let bin_name = match cmd.get_subcommands().next() {
+        Some(subcommand) => format!("cargo-{}", subcommand.get_name()),
+        None => anyhow::bail!("`{}` has no subcommand to complete", cmd.get_name()),
+    };
+    clap_complete::generate(shell, &mut cmd.clone(), bin_name, out);
+    if shell == Shell::Fish {
+        clap_complete::generate(shell, &mut cmd.clone(), "cargo", out);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command() -> clap::Command {
+        clap::Command::new("cargo").subcommand(
+            clap::Command::new("upgrade").arg(
+                clap::Arg::new("bump")
+                    .long("bump")
+                    .value_parser(["major", "minor", "patch"]),
+            ),
+        )
+    }
+
+    #[test]
+    fn completes_binary() {
+        let mut out = Vec::new();
+        write_completions(Shell::Bash, command(), &mut out).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("cargo-upgrade"), "{script}");
+        assert!(script.contains("major minor patch"), "{script}");
+    }
+
+    #[test]
+    fn fish_completes_cargo_subcommand() {
+        let mut out = Vec::new();
+        write_completions(Shell::Fish, command(), &mut out).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("complete -c cargo-upgrade"), "{script}");
+        assert!(script.contains("complete -c cargo "), "{script}");
+    }
+}
@@ -0,0 +1,327 @@
+//! Defaults for the commands, from the workspace and the user's cargo home.
+use std::path::{Path, PathBuf};
+
+use super::errors::*;
+use super::metadata::WorkspaceMetadata;
+use super::util::shell_warn;
+
+const WORKSPACE_KEY: &str = "workspace.metadata.cargo-edit";
+const USER_FILE: &str = "cargo-edit.toml";
+
+/// Defaults for the cargo-edit commands, used where no flag is passed
+///
+/// Read from `[workspace.metadata.cargo-edit]` in the root manifest of the workspace, falling
+/// back to `$CARGO_HOME/cargo-edit.toml`, e.g.
+/// ```toml
+/// [workspace.metadata.cargo-edit]
+/// update-lockfile = false
+///
+/// [workspace.metadata.cargo-edit.upgrade]
+/// exclude = ["serde"]
+/// pinned = "allow"
+/// ```
+/// Unknown keys are warned about and otherwise ignored, so newer configurations keep working.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditConfig {
+    /// Whether to update `Cargo.lock` after editing the manifests, as `rm` does by default
+    pub update_lockfile: Option<bool>,
+    /// Defaults for `cargo upgrade`
+    pub upgrade: UpgradeConfig,
+    /// Defaults for `cargo set-version`
+    pub set_version: SetVersionConfig,
+}
+
+/// Defaults for `cargo upgrade`, from the `upgrade` table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpgradeConfig {
+    /// Dependencies not to upgrade, like `--exclude`
+    pub exclude: Option<Vec<String>>,
+    /// Whether to upgrade pinned requirements, like `--pinned`
+    pub pinned: Option<bool>,
+}
+
+/// Defaults for `cargo set-version`, from the `set-version` table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetVersionConfig {
+    /// Packages not to change, like `--exclude`
+    pub exclude: Option<Vec<String>>,
+}
+
+impl EditConfig {
+    /// Read the defaults for the workspace `manifest_path` is in, or the current directory's
+    ///
+    /// If the workspace can't be loaded, only the user's defaults are read, leaving the error to
+    /// the command itself.
+    pub fn load(manifest_path: Option<&Path>) -> CargoResult<Self> {
+        match WorkspaceMetadata::load_members(manifest_path) {
+            Ok(workspace) => Self::load_from_workspace(&workspace),
+            Err(_) => Self::load_user(),
+        }
+    }
+
+    /// Read the defaults for `workspace`, reusing its metadata
+    pub fn load_from_workspace(workspace: &WorkspaceMetadata) -> CargoResult<Self> {
+        Self::load_from_root(&workspace.root_manifest_path())
+    }
+
+    /// Read the defaults for the workspace whose root manifest is `root_manifest_path`
+    pub fn load_from_root(root_manifest_path: &Path) -> CargoResult<Self> {
+        let user = Self::load_user()?;
+        trace!(
+            "reading configuration from {}",
+            root_manifest_path.display()
+        );
+        let content = std::fs::read_to_string(root_manifest_path)
+            .with_context(|| format!("Failed to read `{}`", root_manifest_path.display()))?;
+        let workspace = Self::from_manifest_str(&content, &mut |warning| {
+            shell_warn(&format!("{warning} in `{}`", root_manifest_path.display()))
+        })?;
+        Ok(workspace.or(user))
+    }
+
+    /// Read `$CARGO_HOME/cargo-edit.toml`, if it exists
+    fn load_user() -> CargoResult<Self> {
+        let path = match user_config_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(Self::default()),
+        };
+        trace!("reading configuration from {}", path.display());
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?;
+        let doc = content
+            .parse::<toml_edit::Document>()
+            .with_context(|| format!("Failed to parse `{}`", path.display()))?;
+        Self::from_table(doc.as_table(), "", &mut |warning| {
+            shell_warn(&format!("{warning} in `{}`", path.display()))
+        })
+        .with_context(|| format!("Invalid configuration in `{}`", path.display()))
+    }
+
+    /// Read `[workspace.metadata.cargo-edit]` from the manifest `content`
+    fn from_manifest_str(
+        content: &str,
+        warn: &mut dyn FnMut(&str) -> CargoResult<()>,
+    ) -> CargoResult<Self> {
+        let doc = content
+            .parse::<toml_edit::Document>()
+            .with_kind(ErrorKind::InvalidManifest)?;
+        let table = doc
+            .get("workspace")
+            .and_then(|w| w.get("metadata"))
+            .and_then(|m| m.get("cargo-edit"));
+        match table {
+            Some(item) => {
+                let table = item
+                    .as_table_like()
+                    .ok_or_else(|| anyhow::format_err!("`{WORKSPACE_KEY}` must be a table"))?;
+                Self::from_table(table, &format!("{WORKSPACE_KEY}."), warn)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Read the defaults from `table`, naming its keys with `prefix` in messages
+    fn from_table(
+        table: &dyn toml_edit::TableLike,
+        prefix: &str,
+        warn: &mut dyn FnMut(&str) -> CargoResult<()>,
+    ) -> CargoResult<Self> {
+        let mut config = Self::default();
+        for (key, item) in table.iter() {
+            let name = format!("{prefix}{key}");
+            match key {
+                "update-lockfile" => config.update_lockfile = Some(bool_value(item, &name)?),
+                "upgrade" => {
+                    for (key, item) in sub_table(item, &name)?.iter() {
+                        let name = format!("{name}.{key}");
+                        match key {
+                            "exclude" => config.upgrade.exclude = Some(str_list(item, &name)?),
+                            "pinned" => {
+                                config.upgrade.pinned = Some(match str_value(item, &name)? {
+                                    "allow" => true,
+                                    "ignore" => false,
+                                    other => anyhow::bail!(
+                                        "Invalid `{name}`: expected `allow` or `ignore`, found `{other}`"
+                                    ),
+                                })
+                            }
+                            _ => warn(&format!("unused config key `{name}`"))?,
+                        }
+                    }
+                }
+                "set-version" => {
+                    for (key, item) in sub_table(item, &name)?.iter() {
+                        let name = format!("{name}.{key}");
+                        match key {
+                            "exclude" => config.set_version.exclude = Some(str_list(item, &name)?),
+                            _ => warn(&format!("unused config key `{name}`"))?,
+                        }
+                    }
+                }
+                _ => warn(&format!("unused config key `{name}`"))?,
+            }
+        }
+        Ok(config)
+    }
+
+    /// Take each default from `self`, or `lower` where `self` doesn't have it
+    fn or(self, lower: Self) -> Self {
+        Self {
+            update_lockfile: self.update_lockfile.or(lower.update_lockfile),
+            upgrade: UpgradeConfig {
+                exclude: self.upgrade.exclude.or(lower.upgrade.exclude),
+                pinned: self.upgrade.pinned.or(lower.upgrade.pinned),
+            },
+            set_version: SetVersionConfig {
+                exclude: self.set_version.exclude.or(lower.set_version.exclude),
+            },
+        }
+    }
+}
+
+/// `$CARGO_HOME/cargo-edit.toml`
+fn user_config_path() -> Option<PathBuf> {
+    home::cargo_home().ok().map(|home| home.join(USER_FILE))
+}
+
+fn sub_table<'i>(
+    item: &'i toml_edit::Item,
+    name: &str,
+) -> CargoResult<&'i dyn toml_edit::TableLike> {
+    item.as_table_like()
+        .ok_or_else(|| anyhow::format_err!("Invalid `{name}`: expected a table"))
+}
+
+fn bool_value(item: &toml_edit::Item, name: &str) -> CargoResult<bool> {
+    item.as_bool()
+        .ok_or_else(|| anyhow::format_err!("Invalid `{name}`: expected `true` or `false`"))
+}
+
+fn str_value<'i>(item: &'i toml_edit::Item, name: &str) -> CargoResult<&'i str> {
+    item.as_str()
+        .ok_or_else(|| anyhow::format_err!("Invalid `{name}`: expected a string"))
+}
+
+fn str_list(item: &toml_edit::Item, name: &str) -> CargoResult<Vec<String>> {
+    let invalid = || anyhow::format_err!("Invalid `{name}`: expected an array of strings");
+    item.as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|value| value.as_str().map(ToOwned::to_owned).ok_or_else(invalid))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(manifest: &str) -> (EditConfig, Vec<String>) {
+        let mut warnings = Vec::new();
+        let config = EditConfig::from_manifest_str(manifest, &mut |warning| {
+            warnings.push(warning.to_owned());
+            Ok(())
+        })
+        .unwrap();
+        (config, warnings)
+    }
+
+    #[test]
+    fn workspace_metadata() {
+        let (config, warnings) = parse(
+            r#"
+[workspace.metadata.cargo-edit]
+update-lockfile = false
+
+[workspace.metadata.cargo-edit.upgrade]
+exclude = ["serde"]
+pinned = "allow"
+
+[workspace.metadata.cargo-edit.set-version]
+exclude = ["xtask"]
+"#,
+        );
+        assert_eq!(
+            config,
+            EditConfig {
+                update_lockfile: Some(false),
+                upgrade: UpgradeConfig {
+                    exclude: Some(vec!["serde".to_owned()]),
+                    pinned: Some(true),
+                },
+                set_version: SetVersionConfig {
+                    exclude: Some(vec!["xtask".to_owned()]),
+                },
+            }
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn no_metadata() {
+        let (config, warnings) = parse("[package]\nname = \"foo\"\n");
+        assert_eq!(config, EditConfig::default());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_warn() {
+        let (config, warnings) = parse(
+            r#"
+[workspace.metadata.cargo-edit]
+rainbow = true
+
+[workspace.metadata.cargo-edit.add]
+requirement-style = "tilde"
+
+[workspace.metadata.cargo-edit.upgrade]
+pinned = "ignore"
+recursive = false
+"#,
+        );
+        assert_eq!(config.upgrade.pinned, Some(false));
+        assert_eq!(
+            warnings,
+            [
+                "unused config key `workspace.metadata.cargo-edit.rainbow`",
+                "unused config key `workspace.metadata.cargo-edit.add`",
+                "unused config key `workspace.metadata.cargo-edit.upgrade.recursive`",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_values_error() {
+        for manifest in [
+            "[workspace.metadata.cargo-edit]\nupdate-lockfile = \"no\"\n",
+            "[workspace.metadata.cargo-edit.upgrade]\npinned = \"skip\"\n",
+            "[workspace.metadata.cargo-edit.upgrade]\nexclude = \"serde\"\n",
+            "[workspace.metadata]\ncargo-edit = 1\n",
+        ] {
+            let result = EditConfig::from_manifest_str(manifest, &mut |_| Ok(()));
+            assert!(result.is_err(), "{manifest}");
+        }
+    }
+
+    #[test]
+    fn workspace_over_user() {
+        let workspace = EditConfig {
+            upgrade: UpgradeConfig {
+                exclude: Some(vec!["serde".to_owned()]),
+                pinned: None,
+            },
+            ..Default::default()
+        };
+        let user = EditConfig {
+            update_lockfile: Some(false),
+            upgrade: UpgradeConfig {
+                exclude: Some(vec!["toml".to_owned()]),
+                pinned: Some(true),
+            },
+            ..Default::default()
+        };
+        let config = workspace.or(user);
+        assert_eq!(config.update_lockfile, Some(false));
+        assert_eq!(config.upgrade.exclude, Some(vec!["serde".to_owned()]));
+        assert_eq!(config.upgrade.pinned, Some(true));
+    }
+}
@@ -1,27 +1,59 @@
 //! Crate name parsing.
+use std::path::{Path, PathBuf};
+
 use super::errors::*;
+use super::{Dependency, GitSource, PathSource, RegistrySource, Source};
 
 /// User-specified crate
 ///
 /// This can be a
 /// - Name (e.g. `docopt`)
-/// - Name and a version req (e.g. `docopt@^0.8`)
-/// - Path
-#[derive(Debug)]
+/// - Name and a version req (e.g. `docopt@^0.8` or `docopt@>=0.8, <0.9`)
+/// - Either, with features to enable (e.g. `serde@1.0+derive+rc`)
+/// - Any of these, from a git repository or a path (e.g. `foo#git=https://github.com/a/foo`)
+///
+/// The grammar is
+/// ```text
+/// spec    = name [ "@" version-req ] *( "+" feature ) [ "#" source ]
+/// source  = "git=" url [ "," ( "branch" / "tag" / "rev" ) "=" reference ]
+///         / "path=" directory
+/// ```
+/// e.g. `foo@0.3+std#git=https://github.com/a/foo,tag=v0.3.1` or `foo#path=../foo`.  The
+/// version requirement of a git or path dependency is the one for when it's published.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CrateSpec {
     /// Crate name
     pub name: String,
     /// Optional version requirement
     pub version_req: Option<String>,
+    /// Features to enable, in the order given
+    pub features: Vec<String>,
+    /// Where the crate comes from, when not from a registry
+    ///
+    /// Paths are kept as given, see [`CrateSpec::to_dependency`].
+    pub source: Option<Source>,
 }
 
 impl CrateSpec {
     /// Convert a string to a `Crate`
     pub fn resolve(pkg_id: &str) -> CargoResult<Self> {
-        let (name, version) = pkg_id
+        let (rest, source) = match pkg_id.split_once('#') {
+            Some((rest, source)) => (rest, Some(parse_source(source)?)),
+            None => (pkg_id, None),
+        };
+        let mut features = rest.split('+');
+        let rest = features.next().unwrap_or_default();
+        let features = features
+            .map(|feature| match feature {
+                "" => Err(anyhow::format_err!("Empty feature in `{pkg_id}`")),
+                feature => Ok(feature.to_owned()),
+            })
+            .collect::<CargoResult<Vec<_>>>()?;
+
+        let (name, version) = rest
             .split_once('@')
             .map(|(n, v)| (n, Some(v)))
-            .unwrap_or((pkg_id, None));
+            .unwrap_or((rest, None));
 
         let invalid: Vec<_> = name
             .chars()
@@ -38,14 +70,44 @@ impl CrateSpec {
 
         if let Some(version) = version {
             semver::VersionReq::parse(version)
-                .with_context(|| format!("Invalid version requirement `{version}`"))?;
+                .with_context(|| format!("Invalid version requirement `{version}`"))
+                .with_kind(ErrorKind::InvalidVersion)?;
         }
 
         Ok(Self {
             name: name.to_owned(),
             version_req: version.map(|s| s.to_owned()),
+            features,
+            source,
         })
     }
+
+    /// The dependency this spec describes, with relative paths resolved against `cwd`
+    pub fn to_dependency(&self, cwd: &Path) -> Dependency {
+        let source = match (&self.source, &self.version_req) {
+            (Some(Source::Path(src)), version) => {
+                let mut src = PathSource::new(cwd.join(&src.path));
+                if let Some(version) = version {
+                    src = src.set_version(version);
+                }
+                Some(Source::Path(src))
+            }
+            (Some(Source::Git(src)), Some(version)) => {
+                Some(Source::Git(src.clone().set_version(version)))
+            }
+            (Some(source), _) => Some(source.clone()),
+            (None, Some(version)) => Some(Source::Registry(RegistrySource::new(version))),
+            (None, None) => None,
+        };
+        let mut dependency = Dependency::new(&self.name);
+        if let Some(source) = source {
+            dependency = dependency.set_source(source);
+        }
+        if !self.features.is_empty() {
+            dependency = dependency.set_features(self.features.clone());
+        }
+        dependency
+    }
 }
 
 impl std::str::FromStr for CrateSpec {
@@ -56,6 +118,172 @@ impl std::str::FromStr for CrateSpec {
     }
 }
 
+fn parse_source(source: &str) -> CargoResult<Source> {
+    if let Some(path) = source.strip_prefix("path=") {
+        if path.is_empty() {
+            anyhow::bail!("Empty path in `#{source}`");
+        }
+        return Ok(Source::Path(PathSource::new(PathBuf::from(path))));
+    }
+    let git = match source.strip_prefix("git=") {
+        Some(git) => git,
+        None => anyhow::bail!("Unknown source `#{source}`, expected `#git=<url>` or `#path=<dir>`"),
+    };
+    let (url, reference) = match git.rsplit_once(',') {
+        Some((url, reference))
+            if ["branch=", "tag=", "rev="]
+                .iter()
+                .any(|key| reference.starts_with(key)) =>
+        {
+            (url, Some(reference))
+        }
+        _ => (git, None),
+    };
+    if url.is_empty() {
+        anyhow::bail!("Empty git URL in `#{source}`");
+    }
+    let mut src = GitSource::new(url);
+    if let Some(reference) = reference {
+        let (key, value) = reference.split_once('=').expect("checked above");
+        if value.is_empty() {
+            anyhow::bail!("Empty {key} in `#{source}`");
+        }
+        src = match key {
+            "branch" => src.set_branch(value),
+            "tag" => src.set_tag(value),
+            _ => src.set_rev(value),
+        };
+    }
+    Ok(Source::Git(src))
+}
+
 fn is_name_char(c: char) -> bool {
     c.is_alphanumeric() || ['-', '_'].contains(&c)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_name_only() {
+        let spec = CrateSpec::resolve("docopt").unwrap();
+        assert_eq!(spec.name, "docopt");
+        assert_eq!(spec.version_req, None);
+    }
+
+    #[test]
+    fn resolve_range_req() {
+        let spec = CrateSpec::resolve("foo@>=1.2, <1.5").unwrap();
+        assert_eq!(spec.name, "foo");
+        assert_eq!(spec.version_req.as_deref(), Some(">=1.2, <1.5"));
+    }
+
+    #[test]
+    fn resolve_invalid_req() {
+        assert!(CrateSpec::resolve("foo@>=1.2 <1.5").is_err());
+        assert!(CrateSpec::resolve("foo@latest").is_err());
+    }
+
+    #[test]
+    fn resolve_features() {
+        let spec = CrateSpec::resolve("serde+derive+rc").unwrap();
+        assert_eq!(spec.name, "serde");
+        assert_eq!(spec.version_req, None);
+        assert_eq!(spec.features, ["derive", "rc"]);
+
+        let spec = CrateSpec::resolve("serde@1.0+derive").unwrap();
+        assert_eq!(spec.version_req.as_deref(), Some("1.0"));
+        assert_eq!(spec.features, ["derive"]);
+        assert_eq!(spec.source, None);
+
+        assert!(CrateSpec::resolve("serde+").is_err());
+        assert!(CrateSpec::resolve("serde++derive").is_err());
+    }
+
+    #[test]
+    fn resolve_git() {
+        let spec = CrateSpec::resolve("foo#git=https://github.com/a/foo").unwrap();
+        assert_eq!(spec.name, "foo");
+        assert_eq!(
+            spec.source,
+            Some(Source::Git(GitSource::new("https://github.com/a/foo")))
+        );
+
+        for (reference, expected) in [
+            (
+                "branch=main",
+                GitSource::new("https://g/foo").set_branch("main"),
+            ),
+            (
+                "tag=v0.3.1",
+                GitSource::new("https://g/foo").set_tag("v0.3.1"),
+            ),
+            (
+                "rev=0a1b2c",
+                GitSource::new("https://g/foo").set_rev("0a1b2c"),
+            ),
+        ] {
+            let spec =
+                CrateSpec::resolve(&format!("foo@0.3+std#git=https://g/foo,{reference}")).unwrap();
+            assert_eq!(spec.version_req.as_deref(), Some("0.3"));
+            assert_eq!(spec.features, ["std"]);
+            assert_eq!(spec.source, Some(Source::Git(expected)));
+        }
+
+        // Only a known key after the last comma is a reference
+        let spec = CrateSpec::resolve("foo#git=https://g/a,b").unwrap();
+        assert_eq!(
+            spec.source,
+            Some(Source::Git(GitSource::new("https://g/a,b")))
+        );
+
+        assert!(CrateSpec::resolve("foo#git=").is_err());
+        assert!(CrateSpec::resolve("foo#git=https://g/foo,tag=").is_err());
+        assert!(CrateSpec::resolve("foo#svn=https://g/foo").is_err());
+    }
+
+    #[test]
+    fn resolve_path() {
+        let spec = CrateSpec::resolve("foo@0.1#path=../foo,bar").unwrap();
+        assert_eq!(spec.version_req.as_deref(), Some("0.1"));
+        assert_eq!(
+            spec.source,
+            Some(Source::Path(PathSource::new("../foo,bar")))
+        );
+        assert!(CrateSpec::resolve("foo#path=").is_err());
+    }
+
+    #[test]
+    fn to_dependency() {
+        let cwd = Path::new("/ws");
+        let dep = CrateSpec::resolve("serde@1.0+derive")
+            .unwrap()
+            .to_dependency(cwd);
+        assert_eq!(
+            dep,
+            Dependency::new("serde")
+                .set_source(RegistrySource::new("1.0"))
+                .set_features(vec!["derive".to_owned()])
+        );
+
+        let dep = CrateSpec::resolve("foo@0.1#path=crates/foo")
+            .unwrap()
+            .to_dependency(cwd);
+        assert_eq!(
+            dep,
+            Dependency::new("foo").set_source(PathSource::new("/ws/crates/foo").set_version("0.1"))
+        );
+
+        let dep = CrateSpec::resolve("foo#git=https://g/foo,tag=v1")
+            .unwrap()
+            .to_dependency(cwd);
+        assert_eq!(
+            dep,
+            Dependency::new("foo").set_source(GitSource::new("https://g/foo").set_tag("v1"))
+        );
+
+        let dep = CrateSpec::resolve("foo").unwrap().to_dependency(cwd);
+        assert_eq!(dep, Dependency::new("foo"));
+    }
+}
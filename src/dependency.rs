@@ -4,7 +4,8 @@ use std::path::{Path, PathBuf};
 use indexmap::IndexSet;
 use toml_edit::KeyMut;
 
-use super::manifest::str_or_1_len_table;
+use super::manifest::{str_or_1_len_table, Manifest};
+use super::paths::{FsResolver, PathResolver};
 use crate::CargoResult;
 
 /// A dependency handled by Cargo
@@ -17,6 +18,8 @@ pub struct Dependency {
     pub name: String,
     /// Whether the dependency is opted-in with a feature flag
     pub optional: Option<bool>,
+    /// Whether the dependency is exposed in the public API (`-Z public-dependency`)
+    pub public: Option<bool>,
 
     /// List of features to add (or None to keep features unchanged).
     pub features: Option<Vec<String>>,
@@ -44,6 +47,7 @@ impl Dependency {
         Self {
             name: name.into(),
             optional: None,
+            public: None,
             features: None,
             default_features: None,
             inherited_features: None,
@@ -76,6 +80,12 @@ impl Dependency {
         self
     }
 
+    /// Set whether the dependency is public
+    pub fn set_public(mut self, public: bool) -> Self {
+        self.public = Some(public);
+        self
+    }
+
     /// Set features as an array of string (does some basic parsing)
     #[allow(dead_code)]
     pub fn set_features(mut self, features: Vec<String>) -> Self {
@@ -109,6 +119,19 @@ impl Dependency {
         self
     }
 
+    /// Change the version requirement, keeping the source
+    ///
+    /// Inherited dependencies have no requirement of their own and are left as is.
+    pub fn set_version(mut self, version: &str) -> Self {
+        self.source = match self.source {
+            Some(Source::Registry(_)) | None => Some(RegistrySource::new(version).into()),
+            Some(Source::Path(src)) => Some(src.set_version(version).into()),
+            Some(Source::Git(src)) => Some(src.set_version(version).into()),
+            source @ Some(Source::Workspace(_)) => source,
+        };
+        self
+    }
+
     /// Set features as an array of string (does some basic parsing)
     pub fn set_inherited_features(mut self, features: Vec<String>) -> Self {
         self.inherited_features = Some(features);
@@ -149,6 +172,66 @@ impl Dependency {
     pub fn optional(&self) -> Option<bool> {
         self.optional
     }
+
+    /// Get whether the dep is public
+    pub fn public(&self) -> Option<bool> {
+        self.public
+    }
+
+    /// Whether the dependency is inherited from `[workspace.dependencies]`
+    pub fn is_inherited(&self) -> bool {
+        matches!(self.source, Some(Source::Workspace(_)))
+    }
+
+    /// Version requirement in effect for the dependency
+    ///
+    /// Inherited dependencies take it from the `[workspace.dependencies]` of `workspace_root`, the
+    /// manifest of the workspace root, so it is `None` for them without one.
+    pub fn effective_version<'m>(
+        &'m self,
+        workspace_root: Option<&'m Manifest>,
+    ) -> Option<&'m str> {
+        if !self.is_inherited() {
+            return self.version();
+        }
+        let item = workspace_root?
+            .data
+            .get("workspace")?
+            .get("dependencies")?
+            .get(self.toml_key())?;
+        item.as_str()
+            .or_else(|| item.get("version").and_then(|version| version.as_str()))
+    }
+
+    /// The dependency as cargo sees it, combined with `workspace_dep`, the entry in
+    /// `[workspace.dependencies]` it inherits
+    ///
+    /// The source comes from the workspace, and so do the default features unless the member
+    /// enables them.  The member's features add to the workspace's, which are kept as the
+    /// `inherited_features`, and only the member decides whether the dependency is optional.
+    /// Dependencies that aren't inherited are returned as is.
+    pub fn resolve_inherited(&self, workspace_dep: &Dependency) -> Self {
+        if !self.is_inherited() {
+            return self.clone();
+        }
+        let inherited = workspace_dep.features.clone().unwrap_or_default();
+        let mut features = inherited.clone();
+        for feature in self.features.iter().flatten() {
+            if !features.contains(feature) {
+                features.push(feature.clone());
+            }
+        }
+
+        let mut resolved = workspace_dep.clone();
+        resolved.features = (!features.is_empty()).then_some(features);
+        resolved.inherited_features = (!inherited.is_empty()).then_some(inherited);
+        resolved.optional = self.optional;
+        // Members can only turn default features back on
+        if self.default_features == Some(true) {
+            resolved.default_features = Some(true);
+        }
+        resolved
+    }
 }
 
 impl Dependency {
@@ -170,8 +253,18 @@ impl Dependency {
                 (key.to_owned(), None)
             };
 
+            // Checked first, so the inheritance is kept even next to keys cargo won't accept
             let source: Source =
-                if let Some(git) = table.get("git") {
+                if let Some(workspace) = table.get("workspace") {
+                    let workspace_bool = workspace.as_bool().ok_or_else(|| {
+                        invalid_type(key, "workspace", workspace.type_name(), "bool")
+                    })?;
+                    if !workspace_bool {
+                        anyhow::bail!("`{key}.workspace = false` is unsupported")
+                    }
+                    let src = WorkspaceSource::new();
+                    src.into()
+                } else if let Some(git) = table.get("git") {
                     let mut src = GitSource::new(
                         git.as_str()
                             .ok_or_else(|| invalid_type(key, "git", git.type_name(), "string"))?,
@@ -214,15 +307,6 @@ impl Dependency {
                         invalid_type(key, "version", version.type_name(), "string")
                     })?);
                     src.into()
-                } else if let Some(workspace) = table.get("workspace") {
-                    let workspace_bool = workspace.as_bool().ok_or_else(|| {
-                        invalid_type(key, "workspace", workspace.type_name(), "bool")
-                    })?;
-                    if !workspace_bool {
-                        anyhow::bail!("`{key}.workspace = false` is unsupported")
-                    }
-                    let src = WorkspaceSource::new();
-                    src.into()
                 } else {
                     anyhow::bail!("Unrecognized dependency source for `{key}`");
                 };
@@ -262,6 +346,7 @@ impl Dependency {
             let available_features = BTreeMap::default();
 
             let optional = table.get("optional").and_then(|v| v.as_bool());
+            let public = table.get("public").and_then(|v| v.as_bool());
 
             let dep = Self {
                 name,
@@ -272,6 +357,7 @@ impl Dependency {
                 features,
                 available_features,
                 optional,
+                public,
                 inherited_features: None,
             };
             Ok(dep)
@@ -303,9 +389,11 @@ impl Dependency {
             "Absolute path needed, got: {}",
             crate_root.display()
         );
+        // An empty feature list is the same as no feature list
+        let features = self.features.as_ref().filter(|f| !f.is_empty());
         let table: toml_edit::Item = match (
-            self.optional.unwrap_or(false),
-            self.features.as_ref(),
+            self.optional.unwrap_or(false) || self.public.unwrap_or(false),
+            features,
             self.default_features.unwrap_or(true),
             self.source.as_ref(),
             self.registry.as_ref(),
@@ -370,15 +458,18 @@ impl Dependency {
                 if self.rename.is_some() {
                     table.insert("package", self.name.as_str().into());
                 }
-                if let Some(v) = self.default_features {
-                    table.insert("default-features", v.into());
+                if self.default_features == Some(false) {
+                    table.insert("default-features", false.into());
                 }
-                if let Some(features) = self.features.as_ref() {
+                if let Some(features) = features {
                     let features: toml_edit::Value = features.iter().cloned().collect();
                     table.insert("features", features);
                 }
-                if let Some(v) = self.optional {
-                    table.insert("optional", v.into());
+                if self.optional == Some(true) {
+                    table.insert("optional", true.into());
+                }
+                if self.public == Some(true) {
+                    table.insert("public", true.into());
                 }
 
                 toml_edit::value(toml_edit::Value::InlineTable(table))
@@ -389,12 +480,49 @@ impl Dependency {
     }
 
     /// Modify existing entry to match this dependency
+    ///
+    /// Only keys whose value changed are touched, so comments, key order and the representation
+    /// of the others are kept.  The entry keeps its [`TableStyle`], except that an inline table
+    /// left holding only a `version` goes back to the short string form.
     pub fn update_toml(&self, crate_root: &Path, key: &mut KeyMut, item: &mut toml_edit::Item) {
-        if str_or_1_len_table(item) {
-            // Nothing to preserve
-            *item = self.to_toml(crate_root);
-            key.fmt();
-        } else if let Some(table) = item.as_table_like_mut() {
+        self.update_toml_with(crate_root, key, item, &FsResolver)
+    }
+
+    /// Like [`Dependency::update_toml`], comparing paths with `resolver` instead of the
+    /// filesystem
+    pub fn update_toml_with(
+        &self,
+        crate_root: &Path,
+        key: &mut KeyMut,
+        item: &mut toml_edit::Item,
+        resolver: &dyn PathResolver,
+    ) {
+        let new = self.to_toml(crate_root);
+        if item.is_str() || (item.is_inline_table() && str_or_1_len_table(item) && !new.is_str()) {
+            // Nothing to preserve but the style and the decor
+            match new {
+                toml_edit::Item::Value(new) if item.is_str() && new.is_str() => {
+                    overwrite_item(item, new);
+                }
+                new => {
+                    let style = TableStyle::of(item);
+                    let decor = item.as_value().map(|v| v.decor().clone());
+                    let mut new = match style {
+                        Some(style) if new.is_table_like() => style.apply(new),
+                        _ => new,
+                    };
+                    if let (Some(decor), Some(value)) = (decor, new.as_value_mut()) {
+                        *value.decor_mut() = decor;
+                    }
+                    *item = new;
+                    key.fmt();
+                }
+            }
+            return;
+        }
+
+        let shrunk = if let Some(table) = item.as_table_like_mut() {
+            let len = table.len();
             match &self.source {
                 Some(Source::Registry(src)) => {
                     overwrite_value(table, "version", src.version.as_str());
@@ -404,8 +532,17 @@ impl Dependency {
                     }
                 }
                 Some(Source::Path(src)) => {
-                    let relpath = path_field(crate_root, &src.path);
-                    overwrite_value(table, "path", relpath);
+                    // Spelling the same path differently, e.g. with a `./`, is no change
+                    let same = table
+                        .get("path")
+                        .and_then(|p| p.as_str())
+                        .map_or(false, |p| {
+                            resolver.same_path(&crate_root.join(p), &src.path)
+                        });
+                    if !same {
+                        let relpath = path_field(crate_root, &src.path);
+                        overwrite_value(table, "path", relpath);
+                    }
                     if let Some(r) = src.version.as_deref() {
                         overwrite_value(table, "version", r);
                     } else {
@@ -445,8 +582,6 @@ impl Dependency {
                 }
                 Some(Source::Workspace(_)) => {
                     overwrite_value(table, "workspace", true);
-                    table.set_dotted(true);
-                    key.fmt();
                     for key in [
                         "version",
                         "registry",
@@ -478,10 +613,13 @@ impl Dependency {
                 overwrite_value(table, "package", self.name.as_str());
             }
             match self.default_features {
-                Some(v) => {
-                    overwrite_value(table, "default-features", v);
+                Some(false) => {
+                    overwrite_value(table, "default-features", false);
                 }
-                None => {
+                // `true` is the default, so drop the key rather than spelling it out, unless
+                // it already was
+                Some(true) if has_bool(table, "default-features", true) => {}
+                Some(true) | None => {
                     table.remove("default-features");
                 }
             }
@@ -497,27 +635,140 @@ impl Dependency {
                     })
                     .unwrap_or_default();
                 features.extend(new_features.iter().map(|s| s.as_str()));
-                let features = features.into_iter().collect::<toml_edit::Value>();
-                table.set_dotted(false);
-                overwrite_value(table, "features", features);
+                if features.is_empty() {
+                    let spelled_out = table
+                        .get("features")
+                        .and_then(|i| i.as_array())
+                        .map_or(false, |a| a.is_empty());
+                    if !spelled_out {
+                        table.remove("features");
+                    }
+                } else {
+                    let features = features.into_iter().collect::<toml_edit::Value>();
+                    overwrite_value(table, "features", features);
+                }
             } else {
                 table.remove("features");
             }
             match self.optional {
-                Some(v) => {
-                    table.set_dotted(false);
-                    overwrite_value(table, "optional", v);
+                Some(true) => {
+                    overwrite_value(table, "optional", true);
                 }
-                None => {
+                Some(false) if has_bool(table, "optional", false) => {}
+                Some(false) | None => {
                     table.remove("optional");
                 }
             }
+            match self.public {
+                Some(true) => {
+                    overwrite_value(table, "public", true);
+                }
+                Some(false) if has_bool(table, "public", false) => {}
+                Some(false) | None => {
+                    table.remove("public");
+                }
+            }
+            table.len() < len
         } else {
             unreachable!("Invalid dependency type: {}", item.type_name());
+        };
+
+        if shrunk && collapse_to_version(item) {
+            key.fmt();
         }
     }
 }
 
+/// How the entry of a dependency is written, when it's a table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TableStyle {
+    /// `dep = { version = "1.0" }`
+    Inline,
+    /// `dep.version = "1.0"`
+    Dotted,
+    /// A `[dependencies.dep]` table of its own
+    Standard,
+}
+
+impl TableStyle {
+    /// Style of the entry `item`, if it's a table
+    pub fn of(item: &toml_edit::Item) -> Option<Self> {
+        match item {
+            toml_edit::Item::Table(table) if table.is_dotted() => Some(Self::Dotted),
+            toml_edit::Item::Table(_) => Some(Self::Standard),
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) if table.is_dotted() => {
+                Some(Self::Dotted)
+            }
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(_)) => Some(Self::Inline),
+            _ => None,
+        }
+    }
+
+    /// Most common style among the entries of `table` that are tables, preferring inline tables
+    pub fn prevailing(table: &dyn toml_edit::TableLike) -> Option<Self> {
+        let styles = table
+            .iter()
+            .filter_map(|(_, item)| Self::of(item))
+            .collect::<Vec<_>>();
+        [Self::Standard, Self::Dotted, Self::Inline]
+            .into_iter()
+            .map(|style| (style, styles.iter().filter(|s| **s == style).count()))
+            .filter(|(_, count)| *count != 0)
+            .max_by_key(|(_, count)| *count)
+            .map(|(style, _)| style)
+    }
+
+    /// Write the entry `item` in this style, if it's a table
+    ///
+    /// A standard table can only go in a standard table, not an inline one.
+    pub fn apply(self, item: toml_edit::Item) -> toml_edit::Item {
+        let mut table = match item {
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => table,
+            toml_edit::Item::Table(table) => table.into_inline_table(),
+            item => return item,
+        };
+        match self {
+            Self::Inline => {
+                table.set_dotted(false);
+                toml_edit::value(table)
+            }
+            Self::Dotted => {
+                table.set_dotted(true);
+                toml_edit::value(table)
+            }
+            Self::Standard => {
+                let mut table = table.into_table();
+                table.set_implicit(false);
+                table.fmt();
+                toml_edit::Item::Table(table)
+            }
+        }
+    }
+}
+
+/// Switch an inline table that only holds a `version` back to the short string form
+pub(crate) fn collapse_to_version(item: &mut toml_edit::Item) -> bool {
+    // Only inline tables collapse, dotted and standard tables keep their style
+    let version = match item.as_inline_table() {
+        Some(table) if table.len() == 1 => table
+            .get("version")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_owned()),
+        _ => None,
+    };
+    match version {
+        Some(version) => {
+            let mut value = toml_edit::Value::from(version);
+            if let Some(existing) = item.as_value() {
+                *value.decor_mut() = existing.decor().clone();
+            }
+            *item = toml_edit::Item::Value(value);
+            true
+        }
+        None => false,
+    }
+}
+
 impl Default for WorkspaceSource {
     fn default() -> Self {
         Self::new()
@@ -530,17 +781,38 @@ fn overwrite_value(
     key: &str,
     value: impl Into<toml_edit::Value>,
 ) {
-    let mut value = value.into();
+    let value = value.into();
 
     let existing = table.entry(key).or_insert(toml_edit::Item::None);
-    let existing_decor = existing
-        .as_value()
-        .map(|v| v.decor().clone())
-        .unwrap_or_default();
+    overwrite_item(existing, value);
+}
+
+/// Overwrite `item` while preserving the original formatting, leaving it as is when unchanged
+fn overwrite_item(item: &mut toml_edit::Item, mut value: toml_edit::Value) {
+    if let Some(existing) = item.as_value() {
+        if same_value(existing, &value) {
+            return;
+        }
+        *value.decor_mut() = existing.decor().clone();
+    }
+
+    *item = toml_edit::Item::Value(value);
+}
 
-    *value.decor_mut() = existing_decor;
+/// Whether `a` and `b` are equal, however they are written
+fn same_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    match (a, b) {
+        (toml_edit::Value::String(a), toml_edit::Value::String(b)) => a.value() == b.value(),
+        (toml_edit::Value::Boolean(a), toml_edit::Value::Boolean(b)) => a.value() == b.value(),
+        (toml_edit::Value::Array(a), toml_edit::Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        _ => false,
+    }
+}
 
-    *existing = toml_edit::Item::Value(value);
+fn has_bool(table: &dyn toml_edit::TableLike, key: &str, value: bool) -> bool {
+    table.get(key).and_then(|i| i.as_bool()) == Some(value)
 }
 
 fn invalid_type(dep: &str, key: &str, actual: &str, expected: &str) -> anyhow::Error {
@@ -863,6 +1135,25 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn to_toml_public_dep() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_public(true);
+        let key = dep.toml_key();
+        let item = dep.to_toml(&crate_root);
+
+        assert_eq!(key, "dep".to_owned());
+        assert!(item.is_inline_table());
+
+        let dep = item.as_inline_table().unwrap();
+        assert_eq!(dep.get("public").unwrap().as_bool(), Some(true));
+
+        verify_roundtrip(&crate_root, key, &item);
+    }
+
     #[test]
     fn to_toml_dep_without_default_features() {
         let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
@@ -1016,6 +1307,275 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn update_toml_disable_default_features() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_default_features(false);
+
+        let expected = "[dependencies]\ndep = { version = \"1.0\", default-features = false }\n";
+        let actual = update_dependencies(&crate_root, &dep, "[dependencies]\ndep = \"1.0\"\n");
+        assert_eq!(actual, expected);
+
+        let actual = update_dependencies(&crate_root, &dep, &actual);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn update_toml_enable_default_features() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_default_features(true);
+
+        let expected = "[dependencies]\ndep = \"1.0\"\n";
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = { version = \"1.0\", default-features = false }\n",
+        );
+        assert_eq!(actual, expected);
+
+        let actual = update_dependencies(&crate_root, &dep, &actual);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn update_toml_enable_default_features_keeps_table() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_default_features(true)
+            .set_optional(true);
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = { version = \"1.0\", default-features = false, optional = true }\n",
+        );
+        assert_eq!(
+            actual,
+            "[dependencies]\ndep = { version = \"1.0\", optional = true }\n"
+        );
+    }
+
+    #[test]
+    fn update_toml_enable_default_features_keeps_standard_table() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_default_features(true);
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies.dep]\nversion = \"1.0\"\ndefault-features = false\n",
+        );
+        assert_eq!(actual, "[dependencies.dep]\nversion = \"1.0\"\n");
+    }
+
+    #[test]
+    fn update_toml_remove_public() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_public(false);
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = { version = \"1.0\", public = true }\n",
+        );
+        assert_eq!(actual, "[dependencies]\ndep = \"1.0\"\n");
+    }
+
+    #[test]
+    fn to_toml_omits_defaults() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_default_features(true)
+            .set_features(vec![])
+            .set_optional(false);
+        let key = dep.toml_key();
+        let item = dep.to_toml(&crate_root);
+
+        assert_eq!(item.as_str(), Some("1.0"));
+
+        verify_roundtrip(&crate_root, key, &item);
+    }
+
+    #[test]
+    fn update_toml_keeps_unchanged_defaults() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_features(vec![])
+            .set_optional(false);
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = { version = \"0.9\", features = [], optional = false }\n",
+        );
+        assert_eq!(
+            actual,
+            "[dependencies]\ndep = { version = \"1.0\", features = [], optional = false }\n"
+        );
+    }
+
+    #[test]
+    fn update_toml_only_touches_changes() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(PathSource::new(crate_root.join("dep")).set_version("2.0"))
+            .set_default_features(true);
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = { path = './dep' , version = \"1.0\"  , default-features = true } # local\n",
+        );
+        assert_eq!(
+            actual,
+            "[dependencies]\ndep = { path = './dep' , version = \"2.0\"  , default-features = true } # local\n"
+        );
+
+        let dep = Dependency::new("dep").set_source(RegistrySource::new("2.0"));
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies]\ndep = '1.0' # pinned by hand\n",
+        );
+        assert_eq!(actual, "[dependencies]\ndep = \"2.0\" # pinned by hand\n");
+        let actual = update_dependencies(&crate_root, &dep, "[dependencies]\ndep = '2.0'\n");
+        assert_eq!(actual, "[dependencies]\ndep = '2.0'\n");
+    }
+
+    #[test]
+    fn update_toml_keeps_table_style() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("2.0"))
+            .set_optional(true);
+
+        for (existing, expected) in [
+            (
+                "[dependencies]\ndep = { version = \"1.0\" }\n",
+                "[dependencies]\ndep = { version = \"2.0\", optional = true }\n",
+            ),
+            (
+                "[dependencies]\ndep.version = \"1.0\"\n",
+                "[dependencies]\ndep.version = \"2.0\"\ndep.optional = true\n",
+            ),
+            (
+                "[dependencies.dep]\nversion = \"1.0\"\n",
+                "[dependencies.dep]\nversion = \"2.0\"\noptional = true\n",
+            ),
+        ] {
+            let actual = update_dependencies(&crate_root, &dep, existing);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn update_toml_keeps_table_with_defaults() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let dep = Dependency::new("dep").set_source(RegistrySource::new("2.0"));
+
+        let actual = update_dependencies(
+            &crate_root,
+            &dep,
+            "[dependencies.dep]\nversion = \"1.0\"\noptional = true\n",
+        );
+        assert_eq!(actual, "[dependencies.dep]\nversion = \"2.0\"\n");
+    }
+
+    #[test]
+    fn inherited_dep_roundtrip() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        for entry in [
+            "dep.workspace = true",
+            "dep = { workspace = true, features = [\"derive\"], optional = true }",
+            "dep = { workspace = true, default-features = false }",
+        ] {
+            let manifest: toml_edit::Document = entry.parse().unwrap();
+            let item = &manifest["dep"];
+            let dep = Dependency::from_toml(&crate_root, "dep", item).unwrap();
+            assert!(dep.is_inherited(), "{entry}");
+
+            let roundtrip = dep.to_toml(&crate_root);
+            let roundtrip = Dependency::from_toml(&crate_root, "dep", &roundtrip).unwrap();
+            assert_eq!(dep, roundtrip, "{entry}");
+        }
+    }
+
+    #[test]
+    fn inherited_dep_keeps_workspace_over_version() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let manifest: toml_edit::Document = "dep = { workspace = true, version = \"1.0\" }"
+            .parse()
+            .unwrap();
+        let dep = Dependency::from_toml(&crate_root, "dep", &manifest["dep"]).unwrap();
+        assert!(dep.is_inherited());
+    }
+
+    #[test]
+    fn resolve_inherited_dep() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let root: Manifest =
+            "[workspace.dependencies]\ndep = { version = \"1.0\", features = [\"std\"] }\n"
+                .parse()
+                .unwrap();
+        let workspace_dep = Dependency::from_toml(
+            &crate_root,
+            "dep",
+            &root.data["workspace"]["dependencies"]["dep"],
+        )
+        .unwrap();
+        let dep = Dependency::new("dep")
+            .set_source(WorkspaceSource::new())
+            .set_features(vec!["derive".to_owned(), "std".to_owned()])
+            .set_optional(true);
+
+        assert_eq!(dep.effective_version(None), None);
+        assert_eq!(dep.effective_version(Some(&root)), Some("1.0"));
+
+        let resolved = dep.resolve_inherited(&workspace_dep);
+        assert_eq!(resolved.version(), Some("1.0"));
+        assert_eq!(
+            resolved.features,
+            Some(vec!["std".to_owned(), "derive".to_owned()])
+        );
+        assert_eq!(resolved.inherited_features, Some(vec!["std".to_owned()]));
+        assert_eq!(resolved.optional, Some(true));
+    }
+
+    fn update_dependencies(crate_root: &Path, dep: &Dependency, manifest: &str) -> String {
+        let mut manifest: toml_edit::Document = manifest.parse().unwrap();
+        let table = manifest["dependencies"].as_table_mut().unwrap();
+        for (mut key, item) in table.iter_mut() {
+            if key.get() == dep.toml_key() {
+                dep.update_toml(crate_root, &mut key, item);
+            }
+        }
+        manifest.to_string()
+    }
+
     #[track_caller]
     fn verify_roundtrip(crate_root: &Path, key: &str, item: &toml_edit::Item) {
         let roundtrip = Dependency::from_toml(crate_root, key, item).unwrap();
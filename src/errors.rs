@@ -66,20 +66,159 @@ impl From<std::io::Error> for CliError {
     }
 }
 
-pub(crate) fn no_crate_err(name: impl Display) -> Error {
-    anyhow::format_err!("The crate `{}` could not be found in registry index.", name)
+/// What went wrong, to tell errors apart without looking at their messages
+///
+/// Kinds are attached to errors with [`WithKind`] and read back with [`ErrorKind::of`].  The
+/// binaries exit with [`ErrorKind::exit_code`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// No `Cargo.toml` was found, exits with 4
+    ManifestNotFound,
+    /// A manifest couldn't be read or parsed, exits with 5
+    InvalidManifest,
+    /// A package ID specification didn't resolve to one package, exits with 6
+    PackageNotFound,
+    /// A registry couldn't be reached or read, so trying again may work, exits with 7
+    RegistryUnavailable,
+    /// A registry doesn't have the crate or a version of it that fits, exits with 8
+    CrateNotFound,
+    /// A version or version requirement is invalid, exits with 9
+    InvalidVersion,
+    /// What was asked for is already the case, e.g. the dependency to remove isn't there, exits
+    /// with 10
+    NothingToDo,
+    /// Anything else, exits with 1
+    Other,
+}
+
+impl ErrorKind {
+    /// The kind attached to `err`, or [`ErrorKind::Other`] if there is none
+    ///
+    /// When several kinds were attached on the way up, the last one wins.
+    pub fn of(err: &Error) -> Self {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<KindError>())
+            .map(|err| err.kind)
+            .unwrap_or(Self::Other)
+    }
+
+    /// Whether the same operation may succeed when tried again
+    pub fn is_transient(self) -> bool {
+        self == Self::RegistryUnavailable
+    }
+
+    /// Process exit code for errors of this kind
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::ManifestNotFound => 4,
+            Self::InvalidManifest => 5,
+            Self::PackageNotFound => 6,
+            Self::RegistryUnavailable => 7,
+            Self::CrateNotFound => 8,
+            Self::InvalidVersion => 9,
+            Self::NothingToDo => 10,
+            Self::Other => 1,
+        }
+    }
+
+    /// Name of the kind, as reported by `--message-format json`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ManifestNotFound => "manifest-not-found",
+            Self::InvalidManifest => "invalid-manifest",
+            Self::PackageNotFound => "package-not-found",
+            Self::RegistryUnavailable => "registry-unavailable",
+            Self::CrateNotFound => "crate-not-found",
+            Self::InvalidVersion => "invalid-version",
+            Self::NothingToDo => "nothing-to-do",
+            Self::Other => "other",
+        }
+    }
+
+    /// Attach this kind to `err`, leaving its messages as they are
+    pub fn attach(self, err: impl Into<Error>) -> Error {
+        KindError {
+            kind: self,
+            error: err.into(),
+        }
+        .into()
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Attach an [`ErrorKind`] to the error of a result
+pub trait WithKind<T> {
+    /// Mark the error, if any, as being of `kind`
+    fn with_kind(self, kind: ErrorKind) -> CargoResult<T>;
+}
+
+impl<T, E: Into<Error>> WithKind<T> for Result<T, E> {
+    fn with_kind(self, kind: ErrorKind) -> CargoResult<T> {
+        self.map_err(|err| kind.attach(err))
+    }
+}
+
+/// An error marked with its kind, showing up just like the error itself
+#[derive(Debug)]
+struct KindError {
+    kind: ErrorKind,
+    error: Error,
+}
+
+impl Display for KindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Not passing on `f`, `{:#}` would print the causes twice
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for KindError {
+    // Skip `error` itself, it is already displayed as this one
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+pub(crate) fn no_crate_err(name: impl Display, suggestions: &[String]) -> Error {
+    let mut message = format!("The crate `{name}` could not be found in registry index.");
+    if !suggestions.is_empty() {
+        let suggestions = suggestions
+            .iter()
+            .map(|s| format!("`{s}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        message.push_str(&format!("\n\nhelp: did you mean {suggestions}?"));
+    }
+    ErrorKind::CrateNotFound.attach(anyhow::format_err!("{}", message))
+}
+
+pub(crate) fn no_vendored_crate_err(name: impl Display, source: impl Display) -> Error {
+    ErrorKind::CrateNotFound.attach(anyhow::format_err!(
+        "The crate `{}` could not be found in the vendored source `{}`; only crates present there can be used.",
+        name,
+        source
+    ))
 }
 
 pub(crate) fn non_existent_table_err(table: impl Display) -> Error {
-    anyhow::format_err!("The table `{}` could not be found.", table)
+    ErrorKind::NothingToDo.attach(anyhow::format_err!(
+        "The table `{}` could not be found.",
+        table
+    ))
 }
 
 pub(crate) fn non_existent_dependency_err(name: impl Display, table: impl Display) -> Error {
-    anyhow::format_err!(
+    ErrorKind::NothingToDo.attach(anyhow::format_err!(
         "The dependency `{}` could not be found in `{}`.",
         name,
         table,
-    )
+    ))
 }
 
 pub(crate) fn invalid_cargo_config() -> Error {
@@ -91,5 +230,49 @@ pub(crate) fn unsupported_version_req(req: impl Display) -> Error {
 }
 
 pub(crate) fn invalid_release_level(actual: impl Display, version: impl Display) -> Error {
-    anyhow::format_err!("Cannot increment the {} field for {}", actual, version)
+    ErrorKind::InvalidVersion.attach(anyhow::format_err!(
+        "Cannot increment the {} field for {}",
+        actual,
+        version
+    ))
+}
+
+pub(crate) fn version_downgrade_err(current: impl Display, requested: impl Display) -> Error {
+    ErrorKind::InvalidVersion.attach(anyhow::format_err!(
+        "Cannot downgrade from {} to {}",
+        current,
+        requested
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error() -> Error {
+        anyhow::format_err!("cause").context("outer")
+    }
+
+    #[test]
+    fn kind_keeps_messages() {
+        let err = ErrorKind::CrateNotFound.attach(error());
+        assert_eq!(err.to_string(), error().to_string());
+        assert_eq!(format!("{err:#}"), format!("{:#}", error()));
+        assert_eq!(format!("{err:?}"), format!("{:?}", error()));
+    }
+
+    #[test]
+    fn kind_of_error() {
+        assert_eq!(ErrorKind::of(&error()), ErrorKind::Other);
+
+        let err = Err::<(), _>(error())
+            .with_kind(ErrorKind::InvalidManifest)
+            .context("while editing")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::InvalidManifest);
+
+        let err = ErrorKind::NothingToDo.attach(err);
+        assert_eq!(ErrorKind::of(&err), ErrorKind::NothingToDo);
+        assert_eq!(ErrorKind::of(&err).exit_code(), 10);
+    }
 }
@@ -1,16 +1,20 @@
 use std::collections::BTreeMap;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use indexmap::IndexSet;
 use url::Url;
 
+use super::cache::LookupCache;
 use super::errors::*;
+use super::local_source::LocalSource;
 use super::registry::registry_url;
 use super::shell_status;
+use super::trace::redact_url;
+use super::version_source::{registry_source, CrateVersion, VersionSource};
 use super::Dependency;
 use super::RegistrySource;
-use super::VersionExt;
 
 /// Query latest version from a registry index
 ///
@@ -59,18 +63,31 @@ pub fn get_latest_dependency(
             .set_available_features(features));
     }
 
-    if crate_name.is_empty() {
-        anyhow::bail!("Found empty crate name");
-    }
-
     let registry = match registry {
         Some(url) => url.clone(),
         None => registry_url(manifest_path, None)?,
     };
+    debug!(
+        "looking up the latest version of `{crate_name}` in {}",
+        redact_url(&registry)
+    );
+    let source = registry_source(&registry)?;
+    get_latest_dependency_from(source.as_ref(), crate_name, flag_allow_prerelease)
+}
 
-    let crate_versions = fuzzy_query_registry_index(crate_name, &registry)?;
-
-    let dep = read_latest_version(&crate_versions, flag_allow_prerelease)?;
+/// Query latest version from `source`
+///
+/// Like [`get_latest_dependency`], for crates coming from somewhere other than a registry.
+pub fn get_latest_dependency_from(
+    source: &dyn VersionSource,
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+) -> CargoResult<Dependency> {
+    let latest = fuzzy_query(source, crate_name, |name| {
+        source.latest_matching(name, None, flag_allow_prerelease)
+    })?;
+    debug!("picked {} {} as the latest", latest.name, latest.version);
+    let dep = version_dependency(latest);
 
     if dep.name != crate_name {
         eprintln!("WARN: Added `{}` instead of `{}`", dep.name, crate_name);
@@ -86,18 +103,33 @@ pub fn get_compatible_dependency(
     manifest_path: &Path,
     registry: Option<&Url>,
 ) -> CargoResult<Dependency> {
-    if crate_name.is_empty() {
-        anyhow::bail!("Found empty crate name");
-    }
-
     let registry = match registry {
         Some(url) => url.clone(),
         None => registry_url(manifest_path, None)?,
     };
+    debug!(
+        "looking up a version of `{crate_name}` matching `{version_req}` in {}",
+        redact_url(&registry)
+    );
+    let source = registry_source(&registry)?;
+    get_compatible_dependency_from(source.as_ref(), crate_name, version_req)
+}
 
-    let crate_versions = fuzzy_query_registry_index(crate_name, &registry)?;
-
-    let dep = read_compatible_version(&crate_versions, version_req)?;
+/// Find the highest version in `source` compatible with a version req
+pub fn get_compatible_dependency_from(
+    source: &dyn VersionSource,
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+) -> CargoResult<Dependency> {
+    // Whether pre-releases match is up to the requirement
+    let latest = fuzzy_query(source, crate_name, |name| {
+        source.latest_matching(name, Some(version_req), true)
+    })?;
+    debug!(
+        "picked {} {} as the latest matching `{version_req}`",
+        latest.name, latest.version
+    );
+    let dep = version_dependency(latest);
 
     if dep.name != crate_name {
         eprintln!("WARN: Added `{}` instead of `{}`", dep.name, crate_name);
@@ -106,47 +138,109 @@ pub fn get_compatible_dependency(
     Ok(dep)
 }
 
-#[derive(Debug)]
-struct CrateVersion {
-    name: String,
-    version: semver::Version,
-    yanked: bool,
-    available_features: BTreeMap<String, Vec<String>>,
-}
-
-/// Fuzzy query crate from registry index
-fn fuzzy_query_registry_index(
-    crate_name: impl Into<String>,
-    registry: &Url,
-) -> CargoResult<Vec<CrateVersion>> {
-    let index = crates_index::Index::from_url(registry.as_str())?;
+/// Fuzzy query crate from `source`, returning the first spelling `query` finds a version for
+fn fuzzy_query(
+    source: &dyn VersionSource,
+    crate_name: &str,
+    mut query: impl FnMut(&str) -> CargoResult<Option<CrateVersion>>,
+) -> CargoResult<CrateVersion> {
+    if crate_name.is_empty() {
+        anyhow::bail!("Found empty crate name");
+    }
 
-    let crate_name = crate_name.into();
-    let mut names = gen_fuzzy_crate_names(crate_name.clone())?;
+    let mut names = gen_fuzzy_crate_names(crate_name.to_owned())?;
     if let Some(index) = names.iter().position(|x| *x == crate_name) {
         // ref: https://github.com/killercup/cargo-edit/pull/317#discussion_r307365704
         names.swap(index, 0);
     }
 
-    for the_name in names {
-        let crate_ = match index.crate_(&the_name) {
-            Some(crate_) => crate_,
-            None => continue,
-        };
-        return crate_
-            .versions()
-            .iter()
-            .map(|v| {
-                Ok(CrateVersion {
-                    name: v.name().to_owned(),
-                    version: v.version().parse()?,
-                    yanked: v.is_yanked(),
-                    available_features: registry_features(v),
-                })
-            })
-            .collect();
+    for the_name in &names {
+        trace!("looking up `{the_name}`");
+        if let Some(version) = query(the_name)? {
+            return Ok(version);
+        }
     }
-    Err(no_crate_err(crate_name))
+    for the_name in &names {
+        if !source.versions(the_name)?.is_empty() {
+            return Err(ErrorKind::CrateNotFound.attach(anyhow::format_err!(
+                "No available versions exist. Either all were yanked \
+                         or only prerelease versions exist. Trying with the \
+                         --allow-prerelease flag might solve the issue."
+            )));
+        }
+    }
+    Err(source.not_found(crate_name))
+}
+
+fn version_dependency(version: CrateVersion) -> Dependency {
+    Dependency::new(&version.name)
+        .set_source(RegistrySource::new(version.version.to_string()))
+        .set_available_features(version.available_features)
+}
+
+/// Maximum number of "did you mean" suggestions reported for an unknown crate
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Maximum number of typo corrections looked up for "did you mean" suggestions
+///
+/// Each one is an index lookup, and long names have dozens of corrections.
+const MAX_SUGGESTION_LOOKUPS: usize = 24;
+
+/// Look up which typo corrections of `crate_name` exist in `source`
+///
+/// Only the most likely [`MAX_SUGGESTION_LOOKUPS`] corrections are tried.
+pub(crate) fn suggest_crate_names<S: VersionSource + ?Sized>(
+    source: &S,
+    crate_name: &str,
+) -> Vec<String> {
+    gen_typo_crate_names(crate_name)
+        .into_iter()
+        .take(MAX_SUGGESTION_LOOKUPS)
+        .filter(|name| {
+            source
+                .versions(name)
+                .map(|versions| !versions.is_empty())
+                .unwrap_or(false)
+        })
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// Generate crate names that are one typo away
+///
+/// Dropping the trailing character is tried first as it is the most common slip, followed by
+/// dropping any other character and swapping adjacent characters.  Each candidate is also
+/// tried with all `-` or all `_` separators.
+///
+/// Examples:
+///
+/// | input | output |
+/// | ----- | ------ |
+/// | serde_jsonn | serde_json, serde-json, serde_jsnn, ... |
+fn gen_typo_crate_names(crate_name: &str) -> Vec<String> {
+    let chars = crate_name.chars().collect::<Vec<_>>();
+    let mut typos = Vec::new();
+    for i in (0..chars.len()).rev() {
+        let mut typo = chars.clone();
+        typo.remove(i);
+        typos.push(typo);
+    }
+    for i in 1..chars.len() {
+        let mut typo = chars.clone();
+        typo.swap(i - 1, i);
+        typos.push(typo);
+    }
+
+    let mut names = IndexSet::new();
+    for typo in typos {
+        let typo = typo.into_iter().collect::<String>();
+        names.insert(typo.replace('-', "_"));
+        names.insert(typo.replace('_', "-"));
+        names.insert(typo);
+    }
+    // Already covered by `gen_fuzzy_crate_names`
+    names.retain(|name| !name.is_empty() && name.replace('-', "_") != crate_name.replace('-', "_"));
+    names.into_iter().collect()
 }
 
 /// Generate all similar crate names
@@ -188,90 +282,72 @@ fn gen_fuzzy_crate_names(crate_name: String) -> CargoResult<Vec<String>> {
     Ok(result)
 }
 
-// Checks whether a version object is a stable release
-fn version_is_stable(version: &CrateVersion) -> bool {
-    !version.version.is_prerelease()
-}
-
-/// Read latest version from Versions structure
-fn read_latest_version(
-    versions: &[CrateVersion],
-    flag_allow_prerelease: bool,
-) -> CargoResult<Dependency> {
-    let latest = versions
-        .iter()
-        .filter(|&v| flag_allow_prerelease || version_is_stable(v))
-        .filter(|&v| !v.yanked)
-        .max_by_key(|&v| v.version.clone())
-        .ok_or_else(|| {
-            anyhow::format_err!(
-                "No available versions exist. Either all were yanked \
-                         or only prerelease versions exist. Trying with the \
-                         --allow-prerelease flag might solve the issue."
-            )
-        })?;
-
-    let name = &latest.name;
-    let version = latest.version.to_string();
-    Ok(Dependency::new(name)
-        .set_source(RegistrySource::new(version))
-        .set_available_features(latest.available_features.clone()))
+/// update registry index for given project
+pub fn update_registry_index(registry: &Url, quiet: bool) -> CargoResult<()> {
+    update_registry_index_with(registry, |update| {
+        // Waiting on another process is always worth mentioning
+        if !quiet || *update == IndexUpdate::Blocking {
+            let (action, message) = update.status();
+            shell_status(action, &message)?;
+        }
+        Ok(())
+    })
 }
 
-fn read_compatible_version(
-    versions: &[CrateVersion],
-    version_req: &semver::VersionReq,
-) -> CargoResult<Dependency> {
-    let latest = versions
-        .iter()
-        .filter(|&v| version_req.matches(&v.version))
-        .filter(|&v| !v.yanked)
-        .max_by_key(|&v| v.version.clone())
-        .ok_or_else(|| {
-            anyhow::format_err!(
-                "No available versions exist. Either all were yanked \
-                         or only prerelease versions exist. Trying with the \
-                         --allow-prerelease flag might solve the issue."
-            )
-        })?;
-
-    let name = &latest.name;
-    let version = latest.version.to_string();
-    Ok(Dependency::new(name)
-        .set_source(RegistrySource::new(version))
-        .set_available_features(latest.available_features.clone()))
-}
+/// Like [`update_registry_index`], with `on_update` told what is being done instead of printing it
+pub fn update_registry_index_with(
+    registry: &Url,
+    mut on_update: impl FnMut(&IndexUpdate) -> CargoResult<()>,
+) -> CargoResult<()> {
+    if let Some(source) = LocalSource::from_url(registry) {
+        // Nothing to update, the source is only ever changed by the user
+        return on_update(&IndexUpdate::Vendored(source.path().to_owned()));
+    }
 
-fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>> {
-    let mut features: BTreeMap<_, _> = v
-        .features()
-        .iter()
-        .map(|(k, v)| (k.clone(), v.clone()))
-        .collect();
-    features.extend(
-        v.dependencies()
-            .iter()
-            .filter(|d| d.is_optional())
-            .map(|d| (d.crate_name().to_owned(), vec![])),
+    let mut index = crates_index::Index::from_url(registry.as_str())
+        .with_kind(ErrorKind::RegistryUnavailable)?;
+    debug!(
+        "updating the index of {} in {}",
+        redact_url(registry),
+        index.path().display()
     );
-    features
-}
-
-/// update registry index for given project
-pub fn update_registry_index(registry: &Url, quiet: bool) -> CargoResult<()> {
-    let mut index = crates_index::Index::from_url(registry.as_str())?;
-    if !quiet {
-        shell_status("Updating", &format!("'{registry}' index"))?;
-    }
+    on_update(&IndexUpdate::Updating(registry.clone()))?;
 
-    while need_retry(index.update())? {
-        shell_status("Blocking", "waiting for lock on registry index")?;
+    while need_retry(index.update()).with_kind(ErrorKind::RegistryUnavailable)? {
+        on_update(&IndexUpdate::Blocking)?;
         std::thread::sleep(REGISTRY_BACKOFF);
     }
+    // Looked up before the update, so possibly missing what it just fetched
+    LookupCache::invalidate(registry);
 
     Ok(())
 }
 
+/// Step of [`update_registry_index_with`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexUpdate {
+    /// The registry is made of the vendored sources in this directory, which are used as is
+    Vendored(PathBuf),
+    /// The index of this registry is being updated
+    Updating(Url),
+    /// Another process holds the lock on the index, waiting for it
+    Blocking,
+}
+
+impl IndexUpdate {
+    /// Status line for the step, as an action and a message
+    pub fn status(&self) -> (&'static str, String) {
+        match self {
+            Self::Vendored(path) => (
+                "Using",
+                format!("vendored sources from '{}'", path.display()),
+            ),
+            Self::Updating(registry) => ("Updating", format!("'{registry}' index")),
+            Self::Blocking => ("Blocking", "waiting for lock on registry index".to_owned()),
+        }
+    }
+}
+
 /// Time between retries for retrieving the registry.
 const REGISTRY_BACKOFF: Duration = Duration::from_secs(1);
 
@@ -312,6 +388,30 @@ fn test_gen_fuzzy_crate_names() {
     );
 }
 
+#[test]
+fn test_gen_typo_crate_names() {
+    let actual = gen_typo_crate_names("serde_jsonn");
+    assert_eq!(actual[0], "serde_json");
+    assert!(actual.contains(&"serde-json".to_owned()));
+    assert!(actual.contains(&"sered_jsonn".to_owned()));
+    assert!(!actual.contains(&"serde_jsonn".to_owned()));
+    assert!(!actual.contains(&"serde-jsonn".to_owned()));
+
+    assert!(gen_typo_crate_names("").is_empty());
+    assert!(gen_typo_crate_names("a").is_empty());
+}
+
+/// Source listing the same versions for every crate name
+#[cfg(test)]
+struct FakeSource(Vec<CrateVersion>);
+
+#[cfg(test)]
+impl VersionSource for FakeSource {
+    fn versions(&self, _name: &str) -> CargoResult<Vec<CrateVersion>> {
+        Ok(self.0.clone())
+    }
+}
+
 #[test]
 fn get_latest_stable_version() {
     let versions = vec![
@@ -320,16 +420,18 @@ fn get_latest_stable_version() {
             version: "0.6.0-alpha".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
         CrateVersion {
             name: "foo".into(),
             version: "0.5.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
     ];
     assert_eq!(
-        read_latest_version(&versions, false)
+        get_latest_dependency_from(&FakeSource(versions), "foo", false)
             .unwrap()
             .version()
             .unwrap(),
@@ -345,16 +447,18 @@ fn get_latest_unstable_or_stable_version() {
             version: "0.6.0-alpha".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
         CrateVersion {
             name: "foo".into(),
             version: "0.5.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
     ];
     assert_eq!(
-        read_latest_version(&versions, true)
+        get_latest_dependency_from(&FakeSource(versions), "foo", true)
             .unwrap()
             .version()
             .unwrap(),
@@ -370,16 +474,18 @@ fn get_latest_version_with_yanked() {
             version: "0.3.1".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
         CrateVersion {
             name: "true".into(),
             version: "0.3.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
     ];
     assert_eq!(
-        read_latest_version(&versions, false)
+        get_latest_dependency_from(&FakeSource(versions), "treexml", false)
             .unwrap()
             .version()
             .unwrap(),
@@ -395,13 +501,75 @@ fn get_no_latest_version_from_json_when_all_are_yanked() {
             version: "0.3.1".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
         CrateVersion {
             name: "true".into(),
             version: "0.3.0".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
         },
     ];
-    assert!(read_latest_version(&versions, false).is_err());
+    assert!(get_latest_dependency_from(&FakeSource(versions), "treexml", false).is_err());
+}
+
+#[test]
+fn get_compatible_version() {
+    let versions = vec![
+        CrateVersion {
+            name: "foo".into(),
+            version: "0.6.0".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+        },
+        CrateVersion {
+            name: "foo".into(),
+            version: "0.5.2".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+        },
+        CrateVersion {
+            name: "foo".into(),
+            version: "0.5.3".parse().unwrap(),
+            yanked: true,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+        },
+    ];
+    let req = semver::VersionReq::parse("0.5").unwrap();
+    assert_eq!(
+        get_compatible_dependency_from(&FakeSource(versions), "foo", &req)
+            .unwrap()
+            .version()
+            .unwrap(),
+        "0.5.2"
+    );
+}
+
+#[test]
+fn get_no_version_from_empty_source() {
+    let err = get_latest_dependency_from(&FakeSource(Vec::new()), "foo", false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The crate `foo` could not be found in registry index."
+    );
+}
+
+#[test]
+fn suggestion_lookups_are_capped() {
+    struct CountingSource(std::cell::Cell<usize>);
+
+    impl VersionSource for CountingSource {
+        fn versions(&self, _name: &str) -> CargoResult<Vec<CrateVersion>> {
+            self.0.set(self.0.get() + 1);
+            Ok(Vec::new())
+        }
+    }
+
+    let source = CountingSource(Default::default());
+    assert!(suggest_crate_names(&source, "a-very_long-crate_name").is_empty());
+    assert_eq!(source.0.get(), MAX_SUGGESTION_LOOKUPS);
 }
@@ -23,28 +23,56 @@
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+mod trace;
+
+mod cache;
+#[cfg(feature = "cli")]
+mod completions;
+mod config;
 mod crate_spec;
 mod dependency;
 mod errors;
 mod fetch;
+mod local_source;
 mod manifest;
 mod metadata;
+pub mod ops;
+mod paths;
 mod registry;
 mod util;
 mod version;
+mod version_source;
 
+#[cfg(feature = "cli")]
+pub use completions::{write_completions, Shell};
+pub use config::{EditConfig, SetVersionConfig, UpgradeConfig};
 pub use crate_spec::CrateSpec;
 pub use dependency::Dependency;
+pub use dependency::GitSource;
 pub use dependency::PathSource;
 pub use dependency::RegistrySource;
 pub use dependency::Source;
+pub use dependency::TableStyle;
 pub use errors::*;
-pub use fetch::{get_compatible_dependency, get_latest_dependency, update_registry_index};
-pub use manifest::{find, get_dep_version, set_dep_version, LocalManifest, Manifest};
-pub use metadata::manifest_from_pkgid;
+pub use fetch::{
+    get_compatible_dependency, get_compatible_dependency_from, get_latest_dependency,
+    get_latest_dependency_from, update_registry_index, update_registry_index_with, IndexUpdate,
+};
+pub use manifest::{
+    find, get_dep_version, set_dep_version, upsert_dependency, upsert_dependency_with, DepKind,
+    DependencyTableMut, FeatureChange, LocalManifest, Manifest, RequirementChange,
+};
+pub use metadata::{
+    is_glob, manifest_from_pkgid, matches_glob, resolve_pkgid, select_packages, MemberFilter,
+    MemberSelection, PackageSelection, SkipReason, WorkspaceMetadata,
+};
+pub use paths::{FsResolver, LexicalResolver, PathResolver};
 pub use registry::registry_url;
+pub use trace::init_tracing;
 pub use util::{
-    colorize_stderr, shell_note, shell_print, shell_status, shell_warn, shell_write_stderr,
-    shell_write_stdout, Color, ColorChoice,
+    colorize_stderr, set_color, shell_note, shell_print, shell_status, shell_warn,
+    shell_write_stderr, shell_write_stdout, Color, ColorChoice, ColorWhen, Table,
 };
-pub use version::{upgrade_requirement, VersionExt};
+pub use version::{upgrade_requirement, BumpLevel, TargetVersion, VersionExt};
+pub use version_source::{registry_source, registry_source_with, CrateVersion, VersionSource};
@@ -0,0 +1,303 @@
+//! Crate metadata from sources on the local filesystem.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use super::errors::*;
+use super::version_source::CrateVersion;
+
+const DIRECTORY_KIND: &str = "directory";
+const LOCAL_REGISTRY_KIND: &str = "local-registry";
+
+/// A source that is read straight from disk, usually set up through source replacement
+///
+/// These are represented as URLs the same way cargo does, e.g. `directory+file:///vendor/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LocalSource {
+    /// Unpacked crates, as produced by `cargo vendor`
+    Directory(PathBuf),
+    /// `.crate` files next to an `index` directory
+    LocalRegistry(PathBuf),
+}
+
+impl LocalSource {
+    /// Recognize a URL created by [`LocalSource::to_url`]
+    pub(crate) fn from_url(url: &Url) -> Option<Self> {
+        let (kind, file_url) = url.as_str().split_once('+')?;
+        let path = Url::parse(file_url).ok()?.to_file_path().ok()?;
+        match kind {
+            DIRECTORY_KIND => Some(Self::Directory(path)),
+            LOCAL_REGISTRY_KIND => Some(Self::LocalRegistry(path)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_url(&self) -> CargoResult<Url> {
+        let kind = match self {
+            Self::Directory(_) => DIRECTORY_KIND,
+            Self::LocalRegistry(_) => LOCAL_REGISTRY_KIND,
+        };
+        let path = self.path();
+        let file_url = Url::from_directory_path(path).map_err(|()| {
+            anyhow::format_err!("Invalid path `{}` for {} source", path.display(), kind)
+        })?;
+        Ok(Url::parse(&format!("{kind}+{file_url}"))?)
+    }
+
+    /// Root of the source on disk
+    pub(crate) fn path(&self) -> &Path {
+        match self {
+            Self::Directory(path) | Self::LocalRegistry(path) => path,
+        }
+    }
+
+    /// All versions of `name` present in this source
+    ///
+    /// An empty list means the source does not contain the crate.
+    pub(crate) fn query(&self, name: &str) -> CargoResult<Vec<CrateVersion>> {
+        match self {
+            Self::Directory(root) => query_directory(root, name),
+            Self::LocalRegistry(root) => query_local_registry(root, name),
+        }
+    }
+}
+
+fn query_directory(root: &Path, name: &str) -> CargoResult<Vec<CrateVersion>> {
+    let entries = std::fs::read_dir(root)
+        .with_context(|| format!("Failed to read vendored source `{}`", root.display()))?;
+
+    // `cargo vendor` uses `<name>` for a single version and `<name>-<version>` otherwise
+    let versioned_prefix = format!("{name}-");
+    let mut versions = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let dir_name = entry.file_name().to_string_lossy().into_owned();
+        if dir_name != name && !dir_name.starts_with(&versioned_prefix) {
+            continue;
+        }
+        let manifest_path = entry.path().join("Cargo.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+        trace!("reading vendored manifest {}", manifest_path.display());
+        let content = std::fs::read_to_string(&manifest_path)?;
+        let manifest = toml::from_str::<VendoredManifest>(&content)
+            .with_context(|| format!("Invalid vendored manifest `{}`", manifest_path.display()))?;
+        if manifest.package.name != name {
+            continue;
+        }
+
+        let mut available_features = manifest.features;
+        available_features.extend(
+            manifest
+                .dependencies
+                .iter()
+                .filter(|(_, dep)| {
+                    dep.get("optional")
+                        .and_then(|optional| optional.as_bool())
+                        .unwrap_or(false)
+                })
+                .map(|(dep_name, _)| (dep_name.clone(), vec![])),
+        );
+        versions.push(CrateVersion {
+            name: manifest.package.name,
+            version: manifest.package.version.parse()?,
+            yanked: false,
+            available_features,
+            rust_version: manifest.package.rust_version,
+        });
+    }
+    Ok(versions)
+}
+
+fn query_local_registry(root: &Path, name: &str) -> CargoResult<Vec<CrateVersion>> {
+    let name = name.to_lowercase();
+    if name.is_empty() || !name.is_ascii() {
+        return Ok(Vec::new());
+    }
+    let path = root.join("index").join(index_file(&name));
+    trace!("reading index file {}", path.display());
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read `{}`", path.display()))
+        }
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry = serde_json::from_str::<IndexEntry>(line)
+                .with_context(|| format!("Invalid index entry in `{}`", path.display()))?;
+            let mut available_features = entry.features;
+            available_features.extend(
+                entry
+                    .deps
+                    .iter()
+                    .filter(|dep| dep.optional)
+                    .map(|dep| (dep.name.clone(), vec![])),
+            );
+            Ok(CrateVersion {
+                name: entry.name,
+                version: entry.vers.parse()?,
+                yanked: entry.yanked,
+                available_features,
+                rust_version: entry.rust_version,
+            })
+        })
+        .collect()
+}
+
+/// Location of a crate's entry within an index, e.g. `se/rd/serde`
+pub(crate) fn index_file(name: &str) -> PathBuf {
+    match name.len() {
+        1 => Path::new("1").join(name),
+        2 => Path::new("2").join(name),
+        3 => Path::new("3").join(&name[..1]).join(name),
+        _ => Path::new(&name[0..2]).join(&name[2..4]).join(name),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VendoredManifest {
+    package: VendoredPackage,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VendoredPackage {
+    name: String,
+    version: String,
+    #[serde(rename = "rust-version", default)]
+    rust_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    #[serde(default)]
+    deps: Vec<IndexDependency>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    rust_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexDependency {
+    name: String,
+    #[serde(default)]
+    optional: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn url_roundtrip() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = dunce::canonicalize(temp.path()).unwrap();
+        for source in [
+            LocalSource::Directory(root.clone()),
+            LocalSource::LocalRegistry(root),
+        ] {
+            let url = source.to_url().unwrap();
+            assert_eq!(LocalSource::from_url(&url), Some(source));
+        }
+
+        let remote = Url::parse("https://github.com/rust-lang/crates.io-index").unwrap();
+        assert_eq!(LocalSource::from_url(&remote), None);
+        let sparse = Url::parse("sparse+https://index.crates.io/").unwrap();
+        assert_eq!(LocalSource::from_url(&sparse), None);
+    }
+
+    #[test]
+    fn directory_versions() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = temp.path();
+        write(
+            &root.join("foo/Cargo.toml"),
+            r#"[package]
+name = "foo"
+version = "0.2.0"
+rust-version = "1.65"
+
+[dependencies.bar]
+version = "1.0"
+optional = true
+
+[features]
+std = []
+"#,
+        );
+        write(
+            &root.join("foo-0.1.0/Cargo.toml"),
+            "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            &root.join("foo-bar/Cargo.toml"),
+            "[package]\nname = \"foo-bar\"\nversion = \"9.0.0\"\n",
+        );
+
+        let source = LocalSource::Directory(root.to_owned());
+        let mut versions = source.query("foo").unwrap();
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version.to_string(), "0.1.0");
+        assert_eq!(versions[1].version.to_string(), "0.2.0");
+        assert_eq!(versions[0].rust_version, None);
+        assert_eq!(versions[1].rust_version.as_deref(), Some("1.65"));
+        assert_eq!(
+            versions[1].available_features.keys().collect::<Vec<_>>(),
+            ["bar", "std"]
+        );
+
+        assert!(source.query("baz").unwrap().is_empty());
+    }
+
+    #[test]
+    fn local_registry_versions() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = temp.path();
+        write(
+            &root.join("index/se/rd/serde"),
+            r#"{"name":"serde","vers":"1.0.0","deps":[],"features":{"std":[]},"cksum":"","yanked":false}
+{"name":"serde","vers":"1.0.1","deps":[{"name":"derive","optional":true}],"features":{},"cksum":"","yanked":true,"rust_version":"1.60"}
+"#,
+        );
+
+        let source = LocalSource::LocalRegistry(root.to_owned());
+        let versions = source.query("serde").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(!versions[0].yanked);
+        assert!(versions[1].yanked);
+        assert!(versions[1].available_features.contains_key("derive"));
+        assert_eq!(versions[0].rust_version, None);
+        assert_eq!(versions[1].rust_version.as_deref(), Some("1.60"));
+
+        assert!(source.query("serde_json").unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_file_layout() {
+        assert_eq!(index_file("a"), Path::new("1/a"));
+        assert_eq!(index_file("ab"), Path::new("2/ab"));
+        assert_eq!(index_file("abc"), Path::new("3/a/abc"));
+        assert_eq!(index_file("serde"), Path::new("se/rd/serde"));
+    }
+}
@@ -1,17 +1,26 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fs;
+use std::io::Write as _;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::{env, str};
 
 use semver::Version;
 
+use super::dependency::{collapse_to_version, Dependency, TableStyle};
 use super::errors::*;
-use super::metadata::find_manifest_path;
+use super::metadata::{find_manifest_path, tried_paths};
+use super::paths::{FsResolver, PathResolver};
+use super::version::upgrade_requirement;
 
+/// Kind of dependency, by the table it is in
 #[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Clone, Debug, Copy)]
 pub enum DepKind {
+    /// `[dependencies]`
     Normal,
+    /// `[dev-dependencies]`
     Development,
+    /// `[build-dependencies]`
     Build,
 }
 
@@ -56,6 +65,19 @@ impl DepTable {
             DepKind::Build => "build-dependencies",
         }
     }
+
+    /// Path of the table within the manifest, e.g. `["target", "cfg(unix)", "dependencies"]`
+    pub(crate) fn to_table(&self) -> Vec<String> {
+        if let Some(target) = &self.target {
+            vec![
+                "target".to_owned(),
+                target.clone(),
+                self.kind_table().to_owned(),
+            ]
+        } else {
+            vec![self.kind_table().to_owned()]
+        }
+    }
 }
 
 impl Default for DepTable {
@@ -71,6 +93,45 @@ impl From<DepKind> for DepTable {
 }
 
 /// A Cargo manifest
+///
+/// Edits only touch the parsed document, so a manifest can be edited without a checkout, e.g.
+/// after fetching it over the network.  Formatting and comments that an edit doesn't touch are
+/// reproduced by `to_string`.  [`LocalManifest`] reads and writes one on disk.
+///
+/// # Examples
+///
+/// ```
+/// use cargo_edit::{Dependency, Manifest, RegistrySource};
+///
+/// let mut manifest: Manifest = r#"[package]
+/// name = "foo"
+/// version = "0.1.0"
+///
+/// [dependencies]
+/// # For the wire format
+/// serde = "1.0"
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// manifest.set_package_version(&semver::Version::new(0, 2, 0));
+/// let crate_root = std::path::Path::new("/foo");
+/// for mut table in manifest.dependency_tables_mut() {
+///     table.upsert(&Dependency::new("regex").set_source(RegistrySource::new("1.8")), crate_root);
+/// }
+/// assert_eq!(
+///     manifest.to_string(),
+///     r#"[package]
+/// name = "foo"
+/// version = "0.2.0"
+///
+/// [dependencies]
+/// # For the wire format
+/// serde = "1.0"
+/// regex = "1.8"
+/// "#
+/// );
+/// ```
 #[derive(Debug, Clone)]
 pub struct Manifest {
     /// Manifest contents as TOML data
@@ -130,6 +191,77 @@ impl Manifest {
         sections
     }
 
+    /// Get the paths of all dependency tables that contain `name`, in the same order as
+    /// the sections are visited.
+    ///
+    /// Each path can be passed on to [`Manifest::remove_from_table`].
+    pub fn get_dependency_table_paths(&self, name: &str) -> Vec<Vec<String>> {
+        self.get_sections()
+            .into_iter()
+            .filter(|(_, item)| {
+                item.as_table_like()
+                    .map(|table| table.contains_key(name))
+                    .unwrap_or(false)
+            })
+            .map(|(table, _)| table.to_table())
+            .collect()
+    }
+
+    /// Get the keys of all dependencies, along with the path of the table each one is in.
+    pub fn get_dependency_keys(&self) -> Vec<(Vec<String>, String)> {
+        self.get_sections()
+            .into_iter()
+            .flat_map(|(table, item)| {
+                let path = table.to_table();
+                item.as_table_like()
+                    .into_iter()
+                    .flat_map(|deps| deps.iter().map(|(key, _)| key.to_owned()))
+                    .map(move |key| (path.clone(), key))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Find the dependencies on the package `name`, whether or not they are renamed.
+    ///
+    /// Returns the path of each table, including `[workspace.dependencies]`, along with the
+    /// key of the dependency in it.
+    pub fn find_package_dependencies(&self, name: &str) -> Vec<(Vec<String>, String)> {
+        let mut tables = self
+            .get_sections()
+            .into_iter()
+            .map(|(table, item)| (table.to_table(), item))
+            .collect::<Vec<_>>();
+        if let Some(item) = self
+            .data
+            .get("workspace")
+            .and_then(|workspace| workspace.get("dependencies"))
+        {
+            tables.push((
+                vec!["workspace".to_owned(), "dependencies".to_owned()],
+                item.clone(),
+            ));
+        }
+
+        let mut found = Vec::new();
+        for (path, item) in tables {
+            let table = match item.as_table_like() {
+                Some(table) => table,
+                None => continue,
+            };
+            for (key, dep) in table.iter() {
+                let package = dep
+                    .get("package")
+                    .and_then(|package| package.as_str())
+                    .unwrap_or(key);
+                if package == name {
+                    found.push((path.clone(), key.to_owned()));
+                }
+            }
+        }
+        found
+    }
+
     fn get_table_mut_internal<'a>(
         &'a mut self,
         table_path: &[String],
@@ -162,77 +294,158 @@ impl Manifest {
 
         descend(self.data.as_item_mut(), table_path, insert_if_not_exists)
     }
-}
-
-impl str::FromStr for Manifest {
-    type Err = anyhow::Error;
-
-    /// Read manifest data from string
-    fn from_str(input: &str) -> ::std::result::Result<Self, Self::Err> {
-        let d: toml_edit::Document = input.parse().context("Manifest not valid TOML")?;
-
-        Ok(Manifest { data: d })
-    }
-}
-
-impl std::fmt::Display for Manifest {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = self.data.to_string();
-        s.fmt(f)
-    }
-}
-
-/// A Cargo manifest that is available locally.
-#[derive(Debug)]
-pub struct LocalManifest {
-    /// Path to the manifest
-    pub path: PathBuf,
-    /// Manifest contents
-    pub manifest: Manifest,
-}
 
-impl Deref for LocalManifest {
-    type Target = Manifest;
-
-    fn deref(&self) -> &Manifest {
-        &self.manifest
-    }
-}
-
-impl DerefMut for LocalManifest {
-    fn deref_mut(&mut self) -> &mut Manifest {
-        &mut self.manifest
+    /// Dependency tables, wherever they live, along with where they are
+    ///
+    /// Besides the standard and `[target]` tables, this covers `[workspace.dependencies]` and
+    /// `[patch.<source>]`.  Tables are visited in the order of the manifest.
+    pub fn dependency_tables_mut(&mut self) -> impl Iterator<Item = DependencyTableMut<'_>> + '_ {
+        let is_kind_table = |key: &str| DepTable::KINDS.iter().any(|kind| kind.kind_table() == key);
+        let mut tables = Vec::new();
+        for (key, item) in self.data.as_table_mut().iter_mut() {
+            let key = key.get().to_owned();
+            match key.as_str() {
+                _ if is_kind_table(&key) => {
+                    if let Some(table) = item.as_table_like_mut() {
+                        tables.push(DependencyTableMut::new(vec![key], table));
+                    }
+                }
+                "workspace" => {
+                    if let Some(table) = item
+                        .get_mut("dependencies")
+                        .and_then(|deps| deps.as_table_like_mut())
+                    {
+                        tables.push(DependencyTableMut::new(
+                            vec![key, "dependencies".to_owned()],
+                            table,
+                        ));
+                    }
+                }
+                "target" => {
+                    let targets = item
+                        .as_table_like_mut()
+                        .into_iter()
+                        .flat_map(|t| t.iter_mut());
+                    for (target, item) in targets {
+                        let target = target.get().to_owned();
+                        let kinds = item
+                            .as_table_like_mut()
+                            .into_iter()
+                            .flat_map(|t| t.iter_mut());
+                        for (kind, item) in kinds {
+                            let kind = kind.get().to_owned();
+                            if !is_kind_table(&kind) {
+                                continue;
+                            }
+                            if let Some(table) = item.as_table_like_mut() {
+                                let path = vec![key.clone(), target.clone(), kind];
+                                tables.push(DependencyTableMut::new(path, table));
+                            }
+                        }
+                    }
+                }
+                "patch" => {
+                    let sources = item
+                        .as_table_like_mut()
+                        .into_iter()
+                        .flat_map(|t| t.iter_mut());
+                    for (source, item) in sources {
+                        if let Some(table) = item.as_table_like_mut() {
+                            let path = vec![key.clone(), source.get().to_owned()];
+                            tables.push(DependencyTableMut::new(path, table));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        tables.into_iter()
     }
-}
 
-impl LocalManifest {
-    /// Construct a `LocalManifest`. If no path is provided, make an educated guess as to which one
-    /// the user means.
-    pub fn find(path: Option<&Path>) -> CargoResult<Self> {
-        let path = dunce::canonicalize(find(path)?)?;
-        Self::try_new(&path)
-    }
+    /// Chains of features that lead to enabling the optional dependency `dep_key`
+    ///
+    /// Each chain starts with a feature and ends with the activation enabling the dependency,
+    /// e.g. `full`, `trace`, `dep:tracing`.  Only the shortest chain is given for each feature.
+    pub fn feature_references(&self, dep_key: &str) -> Vec<Vec<String>> {
+        let features = self.feature_activations();
+        let explicit_dep = format!("dep:{dep_key}");
+        let dep_feature = format!("{dep_key}/");
+        let mut chains = features
+            .iter()
+            .filter_map(|(feature, activations)| {
+                let activation = activations.iter().find(|activation| {
+                    *activation == dep_key
+                        || **activation == explicit_dep
+                        || activation.starts_with(&dep_feature)
+                })?;
+                Some(vec![feature.clone(), activation.clone()])
+            })
+            .collect::<Vec<_>>();
 
-    /// Construct the `LocalManifest` corresponding to the `Path` provided.
-    pub fn try_new(path: &Path) -> CargoResult<Self> {
-        if !path.is_absolute() {
-            anyhow::bail!("can only edit absolute paths, got {}", path.display());
+        let mut idx = 0;
+        while idx < chains.len() {
+            let chain = chains[idx].clone();
+            for (feature, activations) in &features {
+                if activations.contains(&chain[0])
+                    && !chains.iter().any(|other| other[0] == *feature)
+                {
+                    let mut longer = vec![feature.clone()];
+                    longer.extend(chain.iter().cloned());
+                    chains.push(longer);
+                }
+            }
+            idx += 1;
         }
-        let data =
-            std::fs::read_to_string(path).with_context(|| "Failed to read manifest contents")?;
-        let manifest = data.parse().context("Unable to parse Cargo.toml")?;
-        Ok(LocalManifest {
-            manifest,
-            path: path.to_owned(),
-        })
+        chains
     }
 
-    /// Write changes back to the file
-    pub fn write(&self) -> CargoResult<()> {
-        let s = self.manifest.data.to_string();
-        let new_contents_bytes = s.as_bytes();
+    /// Everything each feature enables once the features it activates are expanded
+    ///
+    /// Features are given in the order of the `[features]` table.
+    pub fn feature_expansions(&self) -> Vec<(String, BTreeSet<String>)> {
+        let features = self.feature_activations();
+        features
+            .iter()
+            .map(|(feature, _)| {
+                let mut expansion = BTreeSet::new();
+                let mut seen = BTreeSet::new();
+                let mut pending = vec![feature.as_str()];
+                while let Some(current) = pending.pop() {
+                    if !seen.insert(current) {
+                        continue;
+                    }
+                    match features.iter().find(|(name, _)| name == current) {
+                        Some((_, activations)) => {
+                            pending.extend(activations.iter().map(String::as_str))
+                        }
+                        None => {
+                            expansion.insert(current.to_owned());
+                        }
+                    }
+                }
+                (feature.clone(), expansion)
+            })
+            .collect()
+    }
 
-        std::fs::write(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+    fn feature_activations(&self) -> Vec<(String, Vec<String>)> {
+        let features = match self.data.get("features").and_then(|f| f.as_table_like()) {
+            Some(features) => features,
+            None => return Vec::new(),
+        };
+        features
+            .iter()
+            .map(|(feature, activations)| {
+                let activations = activations
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|activation| activation.as_str())
+                    .map(|activation| activation.to_owned())
+                    .collect();
+                (feature.to_owned(), activations)
+            })
+            .collect()
     }
 
     /// Remove entry from a Cargo.toml.
@@ -240,16 +453,12 @@ impl LocalManifest {
     /// # Examples
     ///
     /// ```
-    ///   use cargo_edit::{Dependency, LocalManifest, Manifest, RegistrySource};
-    ///   use toml_edit;
+    ///   use cargo_edit::Manifest;
     ///
-    ///   let root = std::path::PathBuf::from("/").canonicalize().unwrap();
-    ///   let path = root.join("Cargo.toml");
-    ///   let manifest: toml_edit::Document = "
+    ///   let mut manifest: Manifest = "
     ///   [dependencies]
     ///   cargo-edit = '0.1.0'
     ///   ".parse().unwrap();
-    ///   let mut manifest = LocalManifest { path, manifest: Manifest { data: manifest } };
     ///   assert!(manifest.remove_from_table(&["dependencies".to_owned()], "cargo-edit").is_ok());
     ///   assert!(manifest.remove_from_table(&["dependencies".to_owned()], "cargo-edit").is_err());
     ///   assert!(!manifest.data.contains_key("dependencies"));
@@ -258,22 +467,97 @@ impl LocalManifest {
         let parent_table = self.get_table_mut(table_path)?;
 
         {
-            let dep = parent_table
-                .get_mut(name)
-                .filter(|t| !t.is_none())
-                .ok_or_else(|| non_existent_dependency_err(name, table_path.join(".")))?;
+            let table = parent_table
+                .as_table_like_mut()
+                .expect("`get_table_mut` only returns tables");
+            if !table.contains_key(name) {
+                return Err(non_existent_dependency_err(name, table_path.join(".")));
+            }
             // remove the dependency
-            *dep = toml_edit::Item::None;
+            remove_entry(table, name);
         }
 
         // remove table if empty
         if parent_table.as_table_like().unwrap().is_empty() {
             *parent_table = toml_edit::Item::None;
+            self.remove_empty_target(table_path);
         }
 
         Ok(())
     }
 
+    /// Drop the `[target.<target>]` table (and `[target]`) once its last dependency table is gone
+    fn remove_empty_target(&mut self, table_path: &[String]) {
+        let target = match table_path {
+            [first, target, _] if first == "target" => target,
+            _ => return,
+        };
+        let is_empty = |item: Option<&toml_edit::Item>| {
+            item.and_then(|item| item.as_table_like())
+                .map(|table| table.is_empty())
+                .unwrap_or(false)
+        };
+        if let Some(targets) = self.data.get_mut("target") {
+            if is_empty(targets.get(target)) {
+                targets[target] = toml_edit::Item::None;
+            }
+        }
+        if is_empty(self.data.get("target")) {
+            self.data["target"] = toml_edit::Item::None;
+        }
+    }
+
+    /// Remove `features` from the dependency `name` in the table at `table_path`.
+    ///
+    /// Returns the features that were listed, and so removed.  If no features are left, an
+    /// entry with nothing but a version is switched back to the short string form.
+    pub fn remove_dep_features(
+        &mut self,
+        table_path: &[String],
+        name: &str,
+        features: &[String],
+    ) -> CargoResult<Vec<String>> {
+        let parent_table = self
+            .get_table_mut(table_path)?
+            .as_table_like_mut()
+            .expect("`get_table_mut` only returns tables");
+        let (mut key, dep) = parent_table
+            .iter_mut()
+            .find(|(key, _)| key.get() == name)
+            .ok_or_else(|| non_existent_dependency_err(name, table_path.join(".")))?;
+
+        let mut removed = Vec::new();
+        if let Some(activations) = dep.get_mut("features").and_then(|f| f.as_array_mut()) {
+            let remove_list: Vec<usize> = activations
+                .iter()
+                .enumerate()
+                .filter(|(_, activation)| {
+                    activation
+                        .as_str()
+                        .map(|activation| features.iter().any(|f| f == activation))
+                        .unwrap_or(false)
+                })
+                .map(|(idx, _)| idx)
+                .collect();
+            for idx in remove_list.iter().rev() {
+                if let Some(feature) = remove_array_value(activations, *idx).as_str() {
+                    removed.insert(0, feature.to_owned());
+                }
+            }
+
+            if !removed.is_empty() && activations.is_empty() {
+                if let Some(dep) = dep.as_table_like_mut() {
+                    dep.remove("features");
+                }
+                if collapse_to_version(dep) {
+                    key.fmt();
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
     /// Allow mutating depedencies, wherever they live
     pub fn get_dependency_tables_mut(
         &mut self,
@@ -322,6 +606,14 @@ impl LocalManifest {
         })
     }
 
+    /// Get the `[workspace.dependencies]` table, if any.
+    pub fn get_workspace_dependency_table(&self) -> Option<&dyn toml_edit::TableLike> {
+        self.data
+            .get("workspace")?
+            .get("dependencies")?
+            .as_table_like()
+    }
+
     /// Iterates mutably over the `[workspace.dependencies]`.
     pub fn get_workspace_dependency_table_mut(&mut self) -> Option<&mut dyn toml_edit::TableLike> {
         self.data
@@ -365,26 +657,70 @@ impl LocalManifest {
     }
 
     /// Remove references to `dep_key` if its no longer present
-    pub fn gc_dep(&mut self, dep_key: &str) {
+    ///
+    /// Features that are left empty are removed as well, unless another feature activates them.
+    pub fn gc_dep(&mut self, dep_key: &str) -> Vec<FeatureChange> {
+        let mut changes = Vec::new();
         let status = self.dep_feature(dep_key);
-        if matches!(status, FeatureStatus::None | FeatureStatus::DepFeature) {
-            if let toml_edit::Item::Table(feature_table) = &mut self.data.as_table_mut()["features"]
+        if !matches!(status, FeatureStatus::None | FeatureStatus::DepFeature) {
+            return changes;
+        }
+        let feature_table = match self.data.get_mut("features") {
+            Some(toml_edit::Item::Table(feature_table)) => feature_table,
+            _ => return changes,
+        };
+
+        let mut emptied = Vec::new();
+        for (feature, activated_crates) in feature_table.iter_mut() {
+            if let toml_edit::Item::Value(toml_edit::Value::Array(feature_activations)) =
+                activated_crates
             {
-                for (_feature, mut activated_crates) in feature_table.iter_mut() {
-                    if let toml_edit::Item::Value(toml_edit::Value::Array(feature_activations)) =
-                        &mut activated_crates
-                    {
-                        remove_feature_activation(feature_activations, dep_key, status);
-                    }
+                let removed = remove_feature_activation(feature_activations, dep_key, status);
+                if !removed.is_empty() && feature_activations.is_empty() {
+                    emptied.push(feature.get().to_owned());
                 }
+                changes.extend(removed.into_iter().map(|activation| {
+                    FeatureChange::RemovedActivation {
+                        feature: feature.get().to_owned(),
+                        activation,
+                    }
+                }));
             }
         }
+
+        for feature in emptied {
+            let activated_by = feature_table
+                .iter()
+                .filter(|(_, activations)| {
+                    activations
+                        .as_array()
+                        .map(|a| a.iter().any(|v| v.as_str() == Some(feature.as_str())))
+                        .unwrap_or(false)
+                })
+                .map(|(name, _)| name.to_owned())
+                .collect::<Vec<_>>();
+            if activated_by.is_empty() {
+                remove_entry(feature_table, &feature);
+                changes.push(FeatureChange::RemovedFeature { feature });
+            } else {
+                changes.push(FeatureChange::KeptEmptyFeature {
+                    feature,
+                    activated_by,
+                });
+            }
+        }
+
+        if feature_table.is_empty() {
+            self.data.remove("features");
+        }
+
+        changes
     }
 
     fn dep_feature(&self, dep_key: &str) -> FeatureStatus {
         let mut status = FeatureStatus::None;
         for (_, tbl) in self.get_sections() {
-            if let toml_edit::Item::Table(tbl) = tbl {
+            if let Some(tbl) = tbl.as_table_like() {
                 if let Some(dep_item) = tbl.get(dep_key) {
                     let optional = dep_item.get("optional");
                     let optional = optional.and_then(|i| i.as_value());
@@ -400,6 +736,154 @@ impl LocalManifest {
         }
         status
     }
+
+    /// Upgrade the requirements on the path dependency in `dep_root` to match its new `version`
+    ///
+    /// Relative paths are resolved from `manifest_dir`, the directory of this manifest, and
+    /// compared to `dep_root` with `resolver`.  Dependencies without a requirement, or whose
+    /// requirement already matches `version`, are left alone.
+    pub fn upgrade_path_requirements(
+        &mut self,
+        manifest_dir: &Path,
+        dep_root: &Path,
+        version: &Version,
+        resolver: &dyn PathResolver,
+    ) -> CargoResult<Vec<RequirementChange>> {
+        let mut changes = Vec::new();
+        for mut table in self.dependency_tables_mut() {
+            let mut updates = Vec::new();
+            for (key, item) in table.table().iter() {
+                let dep = match item.as_table_like() {
+                    Some(dep) => dep,
+                    None => continue,
+                };
+                let (old, path) = match (dep.get("version"), dep.get("path")) {
+                    (Some(old), Some(path)) => (old.as_str().unwrap_or("*"), path),
+                    _ => continue,
+                };
+                let same = path.as_str().map_or(false, |path| {
+                    resolver.same_path(&manifest_dir.join(path), dep_root)
+                });
+                if !same {
+                    continue;
+                }
+                if let Some(new) = upgrade_requirement(old, version)? {
+                    let dependency = Dependency::from_toml(manifest_dir, key, item)?;
+                    updates.push(dependency.set_version(&new));
+                    changes.push(RequirementChange {
+                        table: table.path().to_vec(),
+                        dependency: key.to_owned(),
+                        old: old.to_owned(),
+                        new,
+                    });
+                }
+            }
+            for dependency in updates {
+                table.upsert_with(&dependency, manifest_dir, resolver);
+            }
+        }
+        Ok(changes)
+    }
+
+    /// The manifest as TOML, laid out with the line endings of `original`, which it was parsed
+    /// from
+    ///
+    /// Lines that are in `original` keep the ending they had there, edited ones get its most
+    /// common one, and the file ends with a newline only if `original` did.
+    pub fn to_string_like(&self, original: &str) -> String {
+        match_line_endings(original, &self.data.to_string())
+    }
+}
+
+impl str::FromStr for Manifest {
+    type Err = anyhow::Error;
+
+    /// Read manifest data from string
+    fn from_str(input: &str) -> ::std::result::Result<Self, Self::Err> {
+        let d: toml_edit::Document = input
+            .parse()
+            .context("Manifest not valid TOML")
+            .with_kind(ErrorKind::InvalidManifest)?;
+
+        Ok(Manifest { data: d })
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = self.data.to_string();
+        s.fmt(f)
+    }
+}
+
+/// A Cargo manifest that is available locally.
+#[derive(Debug)]
+pub struct LocalManifest {
+    /// Path to the manifest
+    pub path: PathBuf,
+    /// Manifest contents
+    pub manifest: Manifest,
+}
+
+impl Deref for LocalManifest {
+    type Target = Manifest;
+
+    fn deref(&self) -> &Manifest {
+        &self.manifest
+    }
+}
+
+impl DerefMut for LocalManifest {
+    fn deref_mut(&mut self) -> &mut Manifest {
+        &mut self.manifest
+    }
+}
+
+impl LocalManifest {
+    /// Construct a `LocalManifest`. If no path is provided, make an educated guess as to which one
+    /// the user means.
+    pub fn find(path: Option<&Path>) -> CargoResult<Self> {
+        let path = dunce::canonicalize(find(path)?)?;
+        Self::try_new(&path)
+    }
+
+    /// Construct the `LocalManifest` corresponding to the `Path` provided.
+    pub fn try_new(path: &Path) -> CargoResult<Self> {
+        if !path.is_absolute() {
+            anyhow::bail!("can only edit absolute paths, got {}", path.display());
+        }
+        let data = std::fs::read_to_string(path).map_err(|err| {
+            let kind = if err.kind() == std::io::ErrorKind::NotFound {
+                ErrorKind::ManifestNotFound
+            } else {
+                ErrorKind::InvalidManifest
+            };
+            kind.attach(anyhow::Error::new(err).context("Failed to read manifest contents"))
+        })?;
+        let manifest = data
+            .parse()
+            .context("Unable to parse Cargo.toml")
+            .with_kind(ErrorKind::InvalidManifest)?;
+        Ok(LocalManifest {
+            manifest,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Write changes back to the file
+    ///
+    /// The file is replaced at once, so it's left intact when writing fails.  A symlinked
+    /// manifest is written through to its target.  Line endings and the trailing newline follow
+    /// the file being replaced, with edited lines using its most common line ending.
+    pub fn write(&self) -> CargoResult<()> {
+        let s = match fs::read_to_string(&self.path) {
+            Ok(original) => self.manifest.to_string_like(&original),
+            Err(_) => self.manifest.to_string(),
+        };
+        let new_contents_bytes = s.as_bytes();
+
+        write_atomic(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -409,12 +893,142 @@ enum FeatureStatus {
     Feature,
 }
 
+/// Dependency table of a manifest, from [`Manifest::dependency_tables_mut`]
+pub struct DependencyTableMut<'m> {
+    path: Vec<String>,
+    table: &'m mut dyn toml_edit::TableLike,
+}
+
+impl std::fmt::Debug for DependencyTableMut<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DependencyTableMut")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'m> DependencyTableMut<'m> {
+    fn new(path: Vec<String>, table: &'m mut dyn toml_edit::TableLike) -> Self {
+        Self { path, table }
+    }
+
+    /// Path of the table within the manifest, e.g. `["target", "cfg(unix)", "dependencies"]`
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// Kind of the dependencies in the table, `None` for `[patch]` tables
+    pub fn kind(&self) -> Option<DepKind> {
+        if self.patch_source().is_some() {
+            return None;
+        }
+        match self.path.last().map(String::as_str) {
+            Some("dev-dependencies") => Some(DepKind::Development),
+            Some("build-dependencies") => Some(DepKind::Build),
+            _ => Some(DepKind::Normal),
+        }
+    }
+
+    /// Platform of a `[target.<target>]` table
+    pub fn target(&self) -> Option<&str> {
+        match self.path.as_slice() {
+            [target, platform, _] if target == "target" => Some(platform.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the workspace's `[workspace.dependencies]`
+    pub fn is_workspace(&self) -> bool {
+        self.path.first().map(String::as_str) == Some("workspace")
+    }
+
+    /// Source patched by a `[patch.<source>]` table
+    pub fn patch_source(&self) -> Option<&str> {
+        match self.path.as_slice() {
+            [patch, source] if patch == "patch" => Some(source.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The table itself
+    pub fn table(&self) -> &dyn toml_edit::TableLike {
+        &*self.table
+    }
+
+    /// The table itself, for editing
+    pub fn table_mut(&mut self) -> &mut dyn toml_edit::TableLike {
+        &mut *self.table
+    }
+
+    /// Add `dep`, or update its entry while keeping the entry's formatting
+    ///
+    /// See [`upsert_dependency`].
+    pub fn upsert(&mut self, dep: &Dependency, crate_root: &Path) {
+        upsert_dependency(&mut *self.table, dep, crate_root);
+    }
+
+    /// Like [`DependencyTableMut::upsert`], comparing paths with `resolver`
+    pub fn upsert_with(
+        &mut self,
+        dep: &Dependency,
+        crate_root: &Path,
+        resolver: &dyn PathResolver,
+    ) {
+        upsert_dependency_with(&mut *self.table, dep, crate_root, resolver);
+    }
+
+    /// Remove the dependency with the key `name`, keeping the layout of the others tidy
+    pub fn remove(&mut self, name: &str) -> Option<toml_edit::Item> {
+        remove_entry(&mut *self.table, name)
+    }
+}
+
+/// Requirement changed by [`Manifest::upgrade_path_requirements`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequirementChange {
+    /// Path of the dependency table, e.g. `["workspace", "dependencies"]`
+    pub table: Vec<String>,
+    /// Key of the dependency in the table
+    pub dependency: String,
+    /// Requirement before the change
+    pub old: String,
+    /// Requirement after the change
+    pub new: String,
+}
+
+/// Edit made to `[features]` by [`Manifest::gc_dep`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeatureChange {
+    /// `activation` referred to the removed dependency and was dropped from `feature`
+    RemovedActivation {
+        /// Feature that was edited
+        feature: String,
+        /// Entry that was removed, e.g. `dep:foo` or `foo/std`
+        activation: String,
+    },
+    /// `feature` was left empty and was removed
+    RemovedFeature {
+        /// Feature that was removed
+        feature: String,
+    },
+    /// `feature` was left empty but is kept because other features activate it
+    KeptEmptyFeature {
+        /// Feature that was left empty
+        feature: String,
+        /// Features that activate `feature`
+        activated_by: Vec<String>,
+    },
+}
+
+/// Remove activations of `dep`, returning the removed entries
 fn remove_feature_activation(
     feature_activations: &mut toml_edit::Array,
     dep: &str,
     status: FeatureStatus,
-) {
+) -> Vec<String> {
     let dep_feature: &str = &format!("{dep}/",);
+    let weak_dep_feature: &str = &format!("{dep}?/",);
+    let explicit_dep: &str = &format!("dep:{dep}",);
 
     let remove_list: Vec<usize> = feature_activations
         .iter()
@@ -424,8 +1038,13 @@ fn remove_feature_activation(
                 let activation = feature_activation.value();
                 #[allow(clippy::unnecessary_lazy_evaluations)] // requires 1.62
                 match status {
-                    FeatureStatus::None => activation == dep || activation.starts_with(dep_feature),
-                    FeatureStatus::DepFeature => activation == dep,
+                    FeatureStatus::None => {
+                        activation == dep
+                            || activation == explicit_dep
+                            || activation.starts_with(dep_feature)
+                            || activation.starts_with(weak_dep_feature)
+                    }
+                    FeatureStatus::DepFeature => activation == dep || activation == explicit_dep,
                     FeatureStatus::Feature => false,
                 }
                 .then(|| idx)
@@ -435,33 +1054,151 @@ fn remove_feature_activation(
         })
         .collect();
 
+    let removed = remove_list
+        .iter()
+        .filter_map(|idx| feature_activations.get(*idx))
+        .filter_map(|activation| activation.as_str())
+        .map(|activation| activation.to_owned())
+        .collect();
+
     // Remove found idx in revers order so we don't invalidate the idx.
     for idx in remove_list.iter().rev() {
-        feature_activations.remove(*idx);
+        remove_array_value(feature_activations, *idx);
     }
+
+    removed
 }
 
-/// If a manifest is specified, return that one, otherise perform a manifest search starting from
-/// the current directory.
-/// If a manifest is specified, return that one. If a path is specified, perform a manifest search
-/// starting from there. If nothing is specified, start searching from the current directory
-/// (`cwd`).
+/// Remove `key` from `table`, keeping the layout of the remaining entries tidy
+///
+/// Comments in front of the entry go with it.  The blank lines separating it from the previous
+/// entry are kept for the next one, but never right below the table's header, nor doubled up.
+fn remove_entry(table: &mut dyn toml_edit::TableLike, key: &str) -> Option<toml_edit::Item> {
+    let keys = table
+        .iter()
+        .map(|(key, _)| key.to_owned())
+        .collect::<Vec<_>>();
+    let idx = keys.iter().position(|k| k == key)?;
+    let removed_prefix = decor_prefix(table.key_decor(key));
+    let removed = table.remove(key)?;
+
+    // Sub-tables like `[dependencies.foo]` carry their own layout, unlike dotted keys
+    let next = keys.get(idx + 1);
+    let standard = TableStyle::of(&removed) == Some(TableStyle::Standard);
+    if let (false, Some(next)) = (standard, next) {
+        if let Some(decor) = table.key_decor_mut(next) {
+            let prefix = decor_prefix(Some(decor));
+            let (blank, rest) = split_blank_lines(&prefix);
+            let separated = !blank.is_empty() || !split_blank_lines(&removed_prefix).0.is_empty();
+            let blank = if idx != 0 && separated { "\n" } else { "" };
+            decor.set_prefix(format!("{blank}{rest}"));
+        }
+    }
+
+    Some(removed)
+}
+
+fn decor_prefix(decor: Option<&toml_edit::Decor>) -> String {
+    decor
+        .and_then(|decor| decor.prefix())
+        .and_then(|prefix| prefix.as_str())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Split the leading blank lines off a decor prefix
+fn split_blank_lines(prefix: &str) -> (&str, &str) {
+    let blank_len = prefix
+        .split_inclusive('\n')
+        .take_while(|line| line.ends_with('\n') && line.trim().is_empty())
+        .map(|line| line.len())
+        .sum();
+    prefix.split_at(blank_len)
+}
+
+/// Remove the value at `idx` from `array`, keeping the layout of the remaining values tidy
+fn remove_array_value(array: &mut toml_edit::Array, idx: usize) -> toml_edit::Value {
+    let removed = array.remove(idx);
+    if idx == 0 {
+        // Avoid `[ "b"]` when removing from `["a", "b"]`
+        if let Some(first) = array.get_mut(0) {
+            let prefix = decor_prefix(Some(first.decor()));
+            if !prefix.contains('\n') {
+                first.decor_mut().set_prefix("");
+            }
+        }
+    }
+    if array.is_empty() {
+        array.set_trailing("");
+        array.set_trailing_comma(false);
+    }
+    removed
+}
+
+/// If a manifest is specified, return that one, or the `Cargo.toml` in it if it is a directory.
+/// If nothing is specified, search upwards from the current directory (`cwd`), like cargo.
 pub fn find(specified: Option<&Path>) -> CargoResult<PathBuf> {
     match specified {
         Some(path)
             if fs::metadata(path)
-                .with_context(|| "Failed to get cargo file metadata")?
+                .with_context(|| format!("Failed to find manifest {}", path.display()))
+                .with_kind(ErrorKind::ManifestNotFound)?
                 .is_file() =>
         {
             Ok(path.to_owned())
         }
-        Some(path) => find_manifest_path(path),
+        Some(dir) => {
+            let manifest = dir.join("Cargo.toml");
+            if manifest.is_file() {
+                Ok(manifest)
+            } else {
+                Err(ErrorKind::ManifestNotFound.attach(anyhow::format_err!(
+                    "Unable to find Cargo.toml in {}, tried:\n{}",
+                    dir.display(),
+                    tried_paths(&[manifest])
+                )))
+            }
+        }
         None => find_manifest_path(
             &env::current_dir().with_context(|| "Failed to get current directory")?,
         ),
     }
 }
 
+/// Add `dep` to the dependency table `table`, or update its entry
+///
+/// An existing entry only has the keys that changed edited, keeping its comments, key order and
+/// [`TableStyle`].  A new entry follows the style most of the table's entries use.  Paths are
+/// written relative to `crate_root`.
+pub fn upsert_dependency(
+    table: &mut dyn toml_edit::TableLike,
+    dep: &Dependency,
+    crate_root: &Path,
+) {
+    upsert_dependency_with(table, dep, crate_root, &FsResolver)
+}
+
+/// Like [`upsert_dependency`], telling whether an entry's path is already right with `resolver`
+/// instead of the filesystem
+pub fn upsert_dependency_with(
+    table: &mut dyn toml_edit::TableLike,
+    dep: &Dependency,
+    crate_root: &Path,
+    resolver: &dyn PathResolver,
+) {
+    let style = TableStyle::prevailing(table);
+    match table.get_key_value_mut(dep.toml_key()) {
+        Some((mut key, item)) => dep.update_toml_with(crate_root, &mut key, item, resolver),
+        None => {
+            let mut item = dep.to_toml(crate_root);
+            if let Some(style) = style {
+                item = style.apply(item);
+            }
+            table.insert(dep.toml_key(), item);
+        }
+    }
+}
+
 /// Get a dependency's version from its entry in the dependency table
 pub fn get_dep_version(dep_item: &toml_edit::Item) -> CargoResult<&str> {
     if let Some(req) = dep_item.as_str() {
@@ -507,6 +1244,589 @@ fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value
     *item = toml_edit::Item::Value(value);
 }
 
+/// Lay out `new` with the line endings of `original`, which it is an edit of
+///
+/// Lines that are in `original` keep the ending they had there, others get the most common one.
+/// `new` ends with a newline only if `original` did.
+fn match_line_endings(original: &str, new: &str) -> String {
+    let mut endings = HashMap::<&str, VecDeque<&str>>::new();
+    let (mut crlf, mut lf) = (0, 0);
+    for line in original.split_inclusive('\n') {
+        let (content, ending) = split_line_ending(line);
+        match ending {
+            "\r\n" => crlf += 1,
+            "\n" => lf += 1,
+            _ => {}
+        }
+        endings.entry(content).or_default().push_back(ending);
+    }
+    let dominant = if crlf > lf { "\r\n" } else { "\n" };
+
+    let mut result = String::with_capacity(new.len() + crlf);
+    for line in new.split_inclusive('\n') {
+        let (content, ending) = split_line_ending(line);
+        let unchanged = endings
+            .get_mut(content)
+            .and_then(|endings| endings.pop_front());
+        result.push_str(content);
+        if !ending.is_empty() {
+            result.push_str(unchanged.filter(|e| !e.is_empty()).unwrap_or(dominant));
+        }
+    }
+
+    let trailing = original.is_empty() || original.ends_with('\n');
+    if !trailing {
+        let (content, _) = split_line_ending(&result);
+        result.truncate(content.len());
+    } else if !result.is_empty() && !result.ends_with('\n') {
+        result.push_str(dominant);
+    }
+    result
+}
+
+/// Split a line into its content and its ending, if any
+fn split_line_ending(line: &str) -> (&str, &str) {
+    match line.strip_suffix('\n') {
+        Some(content) => {
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            line.split_at(content.len())
+        }
+        None => (line, ""),
+    }
+}
+
+/// Replace the contents of `path` through a temporary file in the same directory
+///
+/// The permissions of the original carry over, as does its ownership where the user may keep it.
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let path = resolve_symlinks(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let original = fs::metadata(&path).ok();
+
+    let (temp_path, mut temp) = create_temp(dir, &file_name)?;
+    let result = (|| {
+        temp.write_all(contents)?;
+        if let Some(original) = &original {
+            preserve_owner(&temp, original);
+            temp.set_permissions(original.permissions())?;
+        }
+        temp.sync_all()?;
+        drop(temp);
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Persist the rename itself, which not every platform allows opening a directory for
+    #[cfg(unix)]
+    {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Follow `path` to the file it ultimately links to, which may not exist yet
+fn resolve_symlinks(path: &Path) -> std::io::Result<PathBuf> {
+    let mut path = path.to_owned();
+    // Same bound as Linux, to not spin on symlink loops
+    for _ in 0..40 {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(dir) => dir.join(target),
+                    None => target,
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        format!("too many levels of symbolic links at {}", path.display()),
+    ))
+}
+
+pub(crate) fn create_temp(dir: &Path, file_name: &str) -> std::io::Result<(PathBuf, fs::File)> {
+    let pid = std::process::id();
+    for attempt in 0..100 {
+        let temp_path = dir.join(format!(".{file_name}.{pid}.{attempt}.tmp"));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(temp) => return Ok((temp_path, temp)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("no temporary file could be created in {}", dir.display()),
+    ))
+}
+
+#[cfg(unix)]
+fn preserve_owner(file: &fs::File, original: &fs::Metadata) {
+    use std::os::unix::fs::{fchown, MetadataExt};
+
+    // Only root may give a file away, but members of its group may keep the group
+    if fchown(file, Some(original.uid()), Some(original.gid())).is_err() {
+        let _ = fchown(file, None, Some(original.gid()));
+    }
+}
+
+#[cfg(not(unix))]
+fn preserve_owner(_file: &fs::File, _original: &fs::Metadata) {}
+
 pub fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
     item.is_str() || item.as_table_like().map(|t| t.len() == 1).unwrap_or(false)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0"
+
+[target.'cfg(unix)'.dev-dependencies]
+libc = "0.2"
+
+[target.'cfg(unix)'.not-dependencies]
+ignored = "0.1"
+
+[workspace.dependencies]
+regex = "1.0"
+
+[patch.crates-io]
+serde = { path = "../serde" }
+"#;
+
+    #[test]
+    fn dependency_tables_with_context() {
+        let mut manifest: Manifest = MANIFEST.parse().unwrap();
+        let tables = manifest
+            .dependency_tables_mut()
+            .map(|table| {
+                (
+                    table.path().join("."),
+                    table.kind(),
+                    table.target().map(ToOwned::to_owned),
+                    table.is_workspace(),
+                    table.patch_source().map(ToOwned::to_owned),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tables,
+            vec![
+                (
+                    "dependencies".to_owned(),
+                    Some(DepKind::Normal),
+                    None,
+                    false,
+                    None
+                ),
+                (
+                    "target.cfg(unix).dev-dependencies".to_owned(),
+                    Some(DepKind::Development),
+                    Some("cfg(unix)".to_owned()),
+                    false,
+                    None
+                ),
+                (
+                    "workspace.dependencies".to_owned(),
+                    Some(DepKind::Normal),
+                    None,
+                    true,
+                    None
+                ),
+                (
+                    "patch.crates-io".to_owned(),
+                    None,
+                    None,
+                    false,
+                    Some("crates-io".to_owned())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn upsert_and_remove_in_tables() {
+        let mut manifest: Manifest = MANIFEST.parse().unwrap();
+        let crate_root = Path::new("/foo");
+        for mut table in manifest.dependency_tables_mut() {
+            if table.target().is_some() {
+                table.upsert(
+                    &Dependency::new("libc").set_source(crate::RegistrySource::new("0.2.140")),
+                    crate_root,
+                );
+            } else if table.patch_source().is_some() {
+                assert!(table.remove("serde").is_some());
+            }
+        }
+        let manifest = manifest.to_string();
+        assert!(manifest.contains("[target.'cfg(unix)'.dev-dependencies]\nlibc = \"0.2.140\"\n"));
+        assert!(!manifest.contains("path = \"../serde\""));
+    }
+
+    #[test]
+    fn write_replaces_contents() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = dunce::canonicalize(temp.path()).unwrap().join("Cargo.toml");
+        std::fs::write(&path, MANIFEST).unwrap();
+
+        let mut manifest = LocalManifest::try_new(&path).unwrap();
+        manifest.set_package_version(&Version::new(0, 2, 0));
+        manifest.write().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("version = \"0.2.0\""), "{written}");
+        let entries = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(entries, 1, "temporary file is left behind");
+    }
+
+    #[test]
+    fn find_accepts_directory() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = dunce::canonicalize(temp.path()).unwrap();
+        let path = root.join("Cargo.toml");
+        std::fs::write(&path, MANIFEST).unwrap();
+        let nested = root.join("src").join("bin");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find(Some(&path)).unwrap(), path);
+        assert_eq!(find(Some(&root)).unwrap(), path);
+        assert_eq!(find_manifest_path(&nested).unwrap(), path);
+
+        // An explicit directory is not searched upwards
+        let err = find(Some(&nested)).unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ManifestNotFound);
+        let tried = nested.join("Cargo.toml");
+        assert!(err.to_string().contains(&*tried.to_string_lossy()), "{err}");
+    }
+
+    #[test]
+    fn find_nearest_of_excluded_package() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = []\nexclude = [\"xtask\"]\n",
+        )
+        .unwrap();
+        let package = root.join("xtask");
+        let nested = package.join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(package.join("Cargo.toml"), MANIFEST).unwrap();
+
+        // Like cargo, the excluded package is its own workspace rather than the root's
+        assert_eq!(
+            find_manifest_path(&nested).unwrap(),
+            package.join("Cargo.toml")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_through_symlink_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = dunce::canonicalize(temp.path()).unwrap();
+        let target = root.join("shared.toml");
+        std::fs::write(&target, MANIFEST).unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let link = root.join("Cargo.toml");
+        std::os::unix::fs::symlink("shared.toml", &link).unwrap();
+
+        let mut manifest = LocalManifest::try_new(&link).unwrap();
+        manifest.set_package_version(&Version::new(0, 2, 0));
+        manifest.write().unwrap();
+
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let written = std::fs::read_to_string(&target).unwrap();
+        assert!(written.contains("version = \"0.2.0\""), "{written}");
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[test]
+    fn line_endings() {
+        // Untouched lines keep their ending, edited ones follow the most common
+        let original = "[package]\r\nname = \"foo\"\r\nversion = \"0.1.0\"\n\r\n[dependencies]\r\n";
+        let new =
+            "[package]\nname = \"foo\"\nversion = \"0.2.0\"\n\n[dependencies]\nserde = \"1.0\"\n";
+        assert_eq!(
+            match_line_endings(original, new),
+            "[package]\r\nname = \"foo\"\r\nversion = \"0.2.0\"\r\n\r\n[dependencies]\r\nserde = \"1.0\"\r\n"
+        );
+
+        let original = "[package]\nname = \"foo\"\r\nversion = \"0.1.0\"\n";
+        let new = "[package]\nname = \"foo\"\nversion = \"0.2.0\"\n";
+        assert_eq!(
+            match_line_endings(original, new),
+            "[package]\nname = \"foo\"\r\nversion = \"0.2.0\"\n"
+        );
+
+        let original = "[package]\nname = \"foo\"";
+        let new = "[package]\nname = \"foo\"\nversion = \"0.2.0\"\n";
+        assert_eq!(
+            match_line_endings(original, new),
+            "[package]\nname = \"foo\"\nversion = \"0.2.0\""
+        );
+
+        let original = "[package]\nname = \"foo\"\n";
+        let new = "[package]\nname = \"bar\"";
+        assert_eq!(
+            match_line_endings(original, new),
+            "[package]\nname = \"bar\"\n"
+        );
+    }
+
+    #[test]
+    fn write_keeps_line_endings() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = dunce::canonicalize(temp.path()).unwrap();
+        for (name, original, expected) in [
+            (
+                "crlf",
+                "[package]\r\nname = \"foo\"\r\nversion = \"0.1.0\"\r\n\r\n[dependencies]\r\nregex = \"1.0\"\r\n",
+                "[package]\r\nname = \"foo\"\r\nversion = \"0.2.0\"\r\n\r\n[dependencies]\r\nregex = \"1.0\"\r\n",
+            ),
+            (
+                "no-trailing-newline",
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\n[dependencies]\nregex = \"1.0\"",
+                "[package]\nname = \"foo\"\nversion = \"0.2.0\"\n\n[dependencies]\nregex = \"1.0\"",
+            ),
+        ] {
+            let path = root.join(name).join("Cargo.toml");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, original).unwrap();
+
+            let mut manifest = LocalManifest::try_new(&path).unwrap();
+            manifest.set_package_version(&Version::new(0, 2, 0));
+            manifest.write().unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected, "{name}");
+
+            // Nothing to change, nothing changes
+            let manifest = LocalManifest::try_new(&path).unwrap();
+            manifest.write().unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn dotted_dependencies() {
+        let mut manifest: Manifest = r#"[package]
+name = "foo"
+
+[dependencies]
+clap = { version = "4.0", features = ["derive"] }
+serde.version = "1.0"
+serde.features = ["derive"]
+toml = "0.7"
+
+[dependencies.regex]
+version = "1.0"
+"#
+        .parse()
+        .unwrap();
+        let crate_root = Path::new("/foo");
+        assert_eq!(
+            manifest.get_dependency_keys(),
+            ["clap", "serde", "toml", "regex"]
+                .map(|key| (vec!["dependencies".to_owned()], key.to_owned()))
+        );
+        assert_eq!(
+            manifest.get_dependency_table_paths("serde"),
+            [vec!["dependencies".to_owned()]]
+        );
+
+        for mut table in manifest.dependency_tables_mut() {
+            table.upsert(
+                &Dependency::new("serde")
+                    .set_source(crate::RegistrySource::new("1.1"))
+                    .set_features(vec!["rc".to_owned()]),
+                crate_root,
+            );
+            table.upsert(
+                &Dependency::new("regex").set_source(crate::RegistrySource::new("1.1")),
+                crate_root,
+            );
+        }
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+
+[dependencies]
+clap = { version = "4.0", features = ["derive"] }
+serde.version = "1.1"
+serde.features = ["derive", "rc"]
+toml = "0.7"
+
+[dependencies.regex]
+version = "1.1"
+"#
+        );
+
+        for mut table in manifest.dependency_tables_mut() {
+            assert!(table.remove("serde").is_some());
+        }
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+
+[dependencies]
+clap = { version = "4.0", features = ["derive"] }
+toml = "0.7"
+
+[dependencies.regex]
+version = "1.1"
+"#
+        );
+    }
+
+    #[test]
+    fn upsert_follows_prevailing_style() {
+        let crate_root = Path::new("/foo");
+        let dep = Dependency::new("new")
+            .set_source(crate::RegistrySource::new("1.0"))
+            .set_optional(true);
+        for (existing, expected) in [
+            (
+                "[dependencies]\nold = { version = \"1.0\", optional = true }\n",
+                "new = { version = \"1.0\", optional = true }\n",
+            ),
+            (
+                "[dependencies]\nold.version = \"1.0\"\nold.optional = true\n",
+                "new.version = \"1.0\"\nnew.optional = true\n",
+            ),
+            (
+                "[dependencies.old]\nversion = \"1.0\"\noptional = true\n",
+                "[dependencies.new]\nversion = \"1.0\"\noptional = true\n",
+            ),
+        ] {
+            let mut manifest: Manifest = format!("[package]\nname = \"foo\"\n\n{existing}")
+                .parse()
+                .unwrap();
+            for mut table in manifest.dependency_tables_mut() {
+                table.upsert(&dep, crate_root);
+            }
+            let manifest = manifest.to_string();
+            assert!(manifest.contains(expected), "{manifest}");
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let manifest: Manifest = MANIFEST.parse().unwrap();
+        assert_eq!(manifest.to_string(), MANIFEST);
+
+        let crlf = MANIFEST.replace('\n', "\r\n");
+        let manifest: Manifest = crlf.parse().unwrap();
+        assert_eq!(manifest.to_string_like(&crlf), crlf);
+    }
+
+    #[test]
+    fn edit_in_memory() {
+        let mut manifest: Manifest = MANIFEST.parse().unwrap();
+        manifest.set_package_version(&Version::new(0, 2, 0));
+        manifest
+            .remove_from_table(&["dependencies".to_owned()], "serde")
+            .unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            MANIFEST
+                .replace("version = \"0.1.0\"", "version = \"0.2.0\"")
+                .replace("[dependencies]\nserde = \"1.0\"\n\n", "")
+        );
+
+        let crlf = MANIFEST.replace('\n', "\r\n");
+        let mut manifest: Manifest = crlf.parse().unwrap();
+        manifest.set_package_version(&Version::new(0, 2, 0));
+        assert_eq!(
+            manifest.to_string_like(&crlf),
+            crlf.replace("version = \"0.1.0\"", "version = \"0.2.0\"")
+        );
+    }
+
+    #[test]
+    fn upgrade_path_requirements_without_filesystem() {
+        let mut manifest: Manifest = r#"[package]
+name = "net"
+
+[dependencies]
+dns = { version = "0.1", path = "../dns" }  # resolver
+http = { version = "0.1", path = "../http" }
+
+[dev-dependencies]
+dns = { version = "=0.1.0", path = "./../net/../dns", features = ["mock"] }
+
+[build-dependencies]
+dns = "0.1"
+"#
+        .parse()
+        .unwrap();
+        // Neither directory exists, so only a lexical comparison keeps the paths as written
+        let changes = manifest
+            .upgrade_path_requirements(
+                Path::new("/ws/crates/net"),
+                Path::new("/ws/crates/dns"),
+                &Version::new(0, 2, 0),
+                &crate::LexicalResolver,
+            )
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                RequirementChange {
+                    table: vec!["dependencies".to_owned()],
+                    dependency: "dns".to_owned(),
+                    old: "0.1".to_owned(),
+                    new: "0.2".to_owned(),
+                },
+                RequirementChange {
+                    table: vec!["dev-dependencies".to_owned()],
+                    dependency: "dns".to_owned(),
+                    old: "=0.1.0".to_owned(),
+                    new: "=0.2.0".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "net"
+
+[dependencies]
+dns = { version = "0.2", path = "../dns" }  # resolver
+http = { version = "0.1", path = "../http" }
+
+[dev-dependencies]
+dns = { version = "=0.2.0", path = "./../net/../dns", features = ["mock"] }
+
+[build-dependencies]
+dns = "0.1"
+"#
+        );
+    }
+}
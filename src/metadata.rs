@@ -1,34 +1,909 @@
 use super::errors::*;
 use cargo_metadata::Package;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use url::Url;
 
 /// Takes a pkgid and attempts to find the path to it's `Cargo.toml`, using `cargo`'s metadata
+///
+/// See [`resolve_pkgid`] for the accepted forms of `pkgid`.
 pub fn manifest_from_pkgid(manifest_path: Option<&Path>, pkgid: &str) -> CargoResult<Package> {
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    if let Some(manifest_path) = manifest_path {
-        cmd.manifest_path(manifest_path);
-    }
-    let result = cmd.exec().with_context(|| "Invalid manifest")?;
-    let packages = result.packages;
-    let package = packages
-        .into_iter()
-        .find(|pkg| pkg.name == pkgid)
-        .with_context(|| {
-            "Found virtual manifest, but this command requires running against an \
-             actual package in this workspace. Try adding `--workspace`."
-        })?;
-    Ok(package)
+    let workspace = WorkspaceMetadata::load_members(manifest_path)?;
+    let package = resolve_pkgid(workspace.members(), pkgid)?;
+    Ok(package.clone())
+}
+
+/// `cargo metadata` of a workspace, run once and shared by everything that needs it
+#[derive(Clone, Debug)]
+pub struct WorkspaceMetadata {
+    metadata: cargo_metadata::Metadata,
+    members: Vec<Package>,
+}
+
+impl WorkspaceMetadata {
+    /// Run `cargo metadata` for the workspace `manifest_path` is in, resolving all features
+    ///
+    /// `manifest_path` may also be the directory of the manifest, see [`find`](crate::find).  This
+    /// updates `Cargo.lock` unless `locked`.  If dependencies can't be resolved, only the members
+    /// are loaded, except when `locked` or `offline` as their failures are then the point.
+    pub fn load(manifest_path: Option<&Path>, locked: bool, offline: bool) -> CargoResult<Self> {
+        let mut cmd = cargo_metadata::MetadataCommand::new();
+        if let Some(manifest_path) = manifest_path {
+            cmd.manifest_path(crate::find(Some(manifest_path))?);
+        }
+        cmd.features(cargo_metadata::CargoOpt::AllFeatures);
+        let mut other = Vec::new();
+        if locked {
+            other.push("--locked".to_owned());
+        }
+        if offline {
+            other.push("--offline".to_owned());
+        }
+        cmd.other_options(other);
+
+        let metadata = match cmd.exec() {
+            Ok(metadata) => metadata,
+            // Failing on `--locked` and `--offline` is what they are for
+            Err(err) if !locked && !offline && is_resolution_error(&err) => {
+                debug!("loading only the members, dependencies failed to resolve: {err}");
+                cmd.no_deps();
+                cmd.exec().with_kind(ErrorKind::InvalidManifest)?
+            }
+            Err(err) => return Err(err).with_kind(ErrorKind::InvalidManifest),
+        };
+        Ok(Self::new(metadata))
+    }
+
+    /// Run `cargo metadata --no-deps`, for when only the members are needed
+    pub fn load_members(manifest_path: Option<&Path>) -> CargoResult<Self> {
+        let mut cmd = cargo_metadata::MetadataCommand::new();
+        cmd.no_deps();
+        if let Some(manifest_path) = manifest_path {
+            cmd.manifest_path(crate::find(Some(manifest_path))?);
+        }
+        let metadata = cmd
+            .exec()
+            .with_context(|| "Invalid manifest")
+            .with_kind(ErrorKind::InvalidManifest)?;
+        Ok(Self::new(metadata))
+    }
+
+    /// Wrap metadata that was already loaded
+    pub fn new(metadata: cargo_metadata::Metadata) -> Self {
+        let ids = metadata
+            .workspace_members
+            .iter()
+            .collect::<std::collections::HashSet<_>>();
+        let members = metadata
+            .packages
+            .iter()
+            .filter(|p| ids.contains(&p.id))
+            .cloned()
+            .collect();
+        Self { metadata, members }
+    }
+
+    /// The metadata itself
+    pub fn metadata(&self) -> &cargo_metadata::Metadata {
+        &self.metadata
+    }
+
+    /// Take the metadata, e.g. for the resolved packages
+    pub fn into_metadata(self) -> cargo_metadata::Metadata {
+        self.metadata
+    }
+
+    /// Packages that are members of the workspace, in the order `cargo metadata` lists them
+    pub fn members(&self) -> &[Package] {
+        &self.members
+    }
+
+    /// Directory of the workspace's root manifest
+    pub fn workspace_root(&self) -> &Path {
+        self.metadata.workspace_root.as_std_path()
+    }
+
+    /// Path of the workspace's root manifest
+    pub fn root_manifest_path(&self) -> PathBuf {
+        self.workspace_root().join("Cargo.toml")
+    }
+
+    /// The members picked by `filter`, along with why the others were skipped
+    pub fn select_members(&self, filter: &MemberFilter) -> CargoResult<MemberSelection<'_>> {
+        let default_members = if filter.respect_default_members {
+            let root = crate::LocalManifest::try_new(&self.root_manifest_path())?;
+            root.data
+                .get("workspace")
+                .and_then(|workspace| workspace.get("default-members"))
+                .and_then(|paths| paths.as_array())
+                .map(|paths| {
+                    paths
+                        .iter()
+                        .filter_map(|path| path.as_str())
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                })
+        } else {
+            None
+        };
+        filter_members(
+            self.members(),
+            self.workspace_root(),
+            default_members.as_deref(),
+            filter,
+        )
+    }
+}
+
+/// Find the one package among `packages` that the package ID specification `pkgid` refers to
+///
+/// Like with `cargo pkgid`, this can be a name, `name@version` (where the version may be
+/// partial, e.g. `1.2`), or a URL with an optional `#name@version` fragment, so what
+/// `cargo pkgid` prints is accepted as is.  A `file://` URL refers to the package in that
+/// directory, others like `registry+https://...` or `git+https://...` to packages from that
+/// source, named after the URL's last segment unless the fragment has a name.  `path:<dir>`
+/// refers to the package in `<dir>`, relative to the current directory.
+pub fn resolve_pkgid<'p>(packages: &'p [Package], pkgid: &str) -> CargoResult<&'p Package> {
+    let cwd = std::env::current_dir().with_context(|| "Failed to get current directory")?;
+    let spec = PkgIdSpec::parse(pkgid, &cwd).with_kind(ErrorKind::PackageNotFound)?;
+    let matches = packages
+        .iter()
+        .filter(|package| spec.matches(package))
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => Err(ErrorKind::PackageNotFound.attach(anyhow::format_err!(
+            "The package ID specification `{pkgid}` did not match any package in the workspace."
+        ))),
+        [package] => {
+            debug!(
+                "`{pkgid}` resolved to {}@{} at {}",
+                package.name, package.version, package.manifest_path
+            );
+            Ok(package)
+        }
+        _ => {
+            let candidates = matches
+                .iter()
+                .map(|package| {
+                    let dir = package
+                        .manifest_path
+                        .parent()
+                        .map(|dir| dir.as_std_path())
+                        .unwrap_or_else(|| Path::new("."));
+                    let dir = pathdiff::diff_paths(dir, &cwd).unwrap_or_else(|| dir.to_owned());
+                    format!(
+                        "  {}@{} (`path:{}`)",
+                        package.name,
+                        package.version,
+                        dir.to_string_lossy().replace('\\', "/")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Err(ErrorKind::PackageNotFound.attach(anyhow::format_err!(
+                "The package ID specification `{pkgid}` is ambiguous, it matches:\n{candidates}"
+            )))
+        }
+    }
+}
+
+/// Packages picked by [`select_packages`], in the order they were given
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackageSelection<'p> {
+    /// Packages to operate on
+    pub selected: Vec<&'p Package>,
+    /// Packages that an excluding selector matched
+    pub excluded: Vec<&'p Package>,
+}
+
+/// Select packages among `packages`, e.g. the workspace members, for `--package` and `--exclude`
+///
+/// A selector is a glob pattern if it has a `*` or `?`, and a package ID specification (see
+/// [`resolve_pkgid`]) otherwise.  Patterns with a `/`, like `crates/net-*`, are matched against
+/// the package's directory relative to `workspace_root`, others like `net-*` against its name.
+/// Selectors in `include` starting with `!` exclude packages, as do all of `exclude`.  If
+/// `include` only excludes, all other packages are selected.  Errors if a selector matches no
+/// package.
+pub fn select_packages<'p>(
+    packages: &'p [Package],
+    workspace_root: &Path,
+    include: &[String],
+    exclude: &[String],
+) -> CargoResult<PackageSelection<'p>> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    let mut any_included = false;
+    let include = include
+        .iter()
+        .map(|selector| match selector.strip_prefix('!') {
+            Some(selector) => (selector, false),
+            None => (selector.as_str(), true),
+        });
+    let exclude = exclude.iter().map(|selector| (selector.as_str(), false));
+    for (selector, is_included) in include.chain(exclude) {
+        let matched = if is_glob(selector) {
+            let matched = packages
+                .iter()
+                .filter(|package| matches_selector_glob(selector, package, workspace_root))
+                .collect::<Vec<_>>();
+            if matched.is_empty() {
+                return Err(ErrorKind::PackageNotFound.attach(anyhow::format_err!(
+                    "The pattern `{selector}` did not match any package in the workspace."
+                )));
+            }
+            debug!(
+                "`{selector}` matched {}",
+                matched
+                    .iter()
+                    .map(|package| package.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            matched
+        } else {
+            vec![resolve_pkgid(packages, selector)?]
+        };
+        if is_included {
+            any_included = true;
+            included.extend(matched);
+        } else {
+            excluded.extend(matched);
+        }
+    }
+
+    let selected = packages
+        .iter()
+        .filter(|package| !any_included || included.iter().any(|p| p.id == package.id))
+        .filter(|package| !excluded.iter().any(|p| p.id == package.id))
+        .collect();
+    let excluded = packages
+        .iter()
+        .filter(|package| excluded.iter().any(|p| p.id == package.id))
+        .collect();
+    Ok(PackageSelection { selected, excluded })
+}
+
+/// Which workspace members to operate on, for [`WorkspaceMetadata::select_members`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemberFilter {
+    /// Selectors of the members to pick, as for [`select_packages`], all of them when empty
+    pub include: Vec<String>,
+    /// Selectors of the members to skip
+    pub exclude: Vec<String>,
+    /// Skip members with `publish = false`
+    pub only_publishable: bool,
+    /// Unless `include` names members, only pick `workspace.default-members` if there are any
+    pub respect_default_members: bool,
+}
+
+/// Why [`WorkspaceMetadata::select_members`] skipped a member
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// An excluding selector matched it
+    Excluded,
+    /// No including selector matched it
+    NotIncluded,
+    /// It is not in `workspace.default-members`
+    NotDefaultMember,
+    /// It has `publish = false`
+    NotPublishable,
+}
+
+/// Members picked by [`WorkspaceMetadata::select_members`], in the order of
+/// [`WorkspaceMetadata::members`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemberSelection<'p> {
+    /// Members to operate on
+    pub selected: Vec<&'p Package>,
+    /// All other members, with the first reason that applies to them
+    pub skipped: Vec<(&'p Package, SkipReason)>,
+}
+
+impl MemberSelection<'_> {
+    /// Whether `package` was skipped for `reason`
+    pub fn is_skipped_for(&self, package: &Package, reason: SkipReason) -> bool {
+        self.skipped
+            .iter()
+            .any(|(skipped, why)| skipped.id == package.id && *why == reason)
+    }
+}
+
+fn filter_members<'p>(
+    members: &'p [Package],
+    workspace_root: &Path,
+    default_members: Option<&[String]>,
+    filter: &MemberFilter,
+) -> CargoResult<MemberSelection<'p>> {
+    let selection = select_packages(members, workspace_root, &filter.include, &filter.exclude)?;
+    // Like for cargo, naming members overrides the default ones
+    let default_members = if filter.include.iter().any(|s| !s.starts_with('!')) {
+        None
+    } else {
+        default_members
+    };
+    let is_default_member = |package: &Package| {
+        let dir = match package.manifest_path.parent() {
+            Some(dir) => dir.as_std_path(),
+            None => return false,
+        };
+        let dir = dir.strip_prefix(workspace_root).unwrap_or(dir);
+        let dir = dir.to_string_lossy().replace('\\', "/");
+        default_members.map_or(true, |paths| {
+            paths.iter().any(|path| {
+                let path = path.trim_start_matches("./").trim_end_matches('/');
+                let path = if path == "." { "" } else { path };
+                matches_glob(path, &dir)
+            })
+        })
+    };
+
+    let mut result = MemberSelection::default();
+    for package in members {
+        let reason = if selection.excluded.iter().any(|p| p.id == package.id) {
+            Some(SkipReason::Excluded)
+        } else if !selection.selected.iter().any(|p| p.id == package.id) {
+            Some(SkipReason::NotIncluded)
+        } else if !is_default_member(package) {
+            Some(SkipReason::NotDefaultMember)
+        } else if filter.only_publishable
+            && package
+                .publish
+                .as_ref()
+                .map_or(false, |registries| registries.is_empty())
+        {
+            Some(SkipReason::NotPublishable)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                trace!("skipping {}: {reason:?}", package.name);
+                result.skipped.push((package, reason));
+            }
+            None => result.selected.push(package),
+        }
+    }
+    Ok(result)
+}
+
+/// Whether `selector` is a glob pattern, for [`select_packages`]
+pub fn is_glob(selector: &str) -> bool {
+    selector.contains(['*', '?'])
+}
+
+/// Whether `text` matches the glob `pattern`
+///
+/// `*` matches any run of characters other than `/`, and `?` any one character but `/`.
+pub fn matches_glob(pattern: &str, text: &str) -> bool {
+    let patterns = pattern.split('/').collect::<Vec<_>>();
+    let texts = text.split('/').collect::<Vec<_>>();
+    patterns.len() == texts.len()
+        && patterns
+            .iter()
+            .zip(texts)
+            .all(|(pattern, text)| matches_segment(pattern, text))
+}
+
+/// Match a glob pattern without any `/`, backtracking to the last `*` on a mismatch
+fn matches_segment(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`, in the pattern and the text
+    let mut resume = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                resume = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match resume {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    resume = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn matches_selector_glob(pattern: &str, package: &Package, workspace_root: &Path) -> bool {
+    if !pattern.contains('/') {
+        return matches_glob(pattern, &package.name);
+    }
+    let dir = match package.manifest_path.parent() {
+        Some(dir) => dir.as_std_path(),
+        None => return false,
+    };
+    let dir = dir.strip_prefix(workspace_root).unwrap_or(dir);
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    matches_glob(pattern, &dir.to_string_lossy().replace('\\', "/"))
+}
+
+/// Whether `cargo metadata` failed to resolve dependencies, rather than on the workspace itself
+fn is_resolution_error(err: &cargo_metadata::Error) -> bool {
+    const RESOLUTION_ERRORS: &[&str] = &[
+        "failed to select a version",
+        "no matching package named",
+        "failed to load source for dependency",
+        "failed to get `",
+        "failed to update",
+    ];
+    match err {
+        cargo_metadata::Error::CargoMetadata { stderr } => RESOLUTION_ERRORS
+            .iter()
+            .any(|needle| stderr.contains(needle)),
+        _ => false,
+    }
+}
+
+/// Parsed package ID specification
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PkgIdSpec {
+    name: Option<String>,
+    /// Possibly partial, e.g. `1.2`
+    version: Option<String>,
+    /// Directory of the package
+    path: Option<PathBuf>,
+    /// Where a package that isn't local comes from
+    source: Option<SourceSpec>,
+}
+
+/// Source of a package ID specification like `registry+https://...` or `git+https://...`
+#[derive(Debug, PartialEq, Eq)]
+struct SourceSpec {
+    /// `git`, `registry` or `sparse`, any of them when not given
+    kind: Option<String>,
+    url: Url,
+}
+
+impl PkgIdSpec {
+    fn parse(spec: &str, cwd: &Path) -> CargoResult<Self> {
+        if let Some(path) = spec.strip_prefix("path:") {
+            return Ok(Self {
+                path: Some(cwd.join(path)),
+                ..Default::default()
+            });
+        }
+        let scheme_end = match spec.find("://") {
+            Some(scheme_end) => scheme_end,
+            None => return Self::parse_name_version(spec, spec),
+        };
+
+        // The kind of source comes first, as in `path+file:///...` printed by `cargo pkgid`
+        let (kind, url) = match spec[..scheme_end].split_once('+') {
+            Some((kind, _)) => (Some(kind), &spec[kind.len() + 1..]),
+            None => (None, spec),
+        };
+        if let Some(kind) = kind {
+            if !["path", "git", "registry", "sparse"].contains(&kind) {
+                anyhow::bail!(
+                    "Unsupported source `{kind}` in package ID specification `{spec}`, expected `path`, `git`, `registry` or `sparse`"
+                );
+            }
+        }
+        let mut url = Url::parse(url)
+            .with_context(|| format!("Invalid package ID specification `{spec}`"))?;
+        let mut parsed = match url.fragment() {
+            // Just a version, with the name being the last path segment
+            Some(fragment) if fragment.starts_with(|c: char| c.is_ascii_digit()) => Self {
+                version: Some(parse_partial_version(fragment, spec)?),
+                ..Default::default()
+            },
+            Some(fragment) => Self::parse_name_version(fragment, spec)?,
+            None => Self::default(),
+        };
+        url.set_fragment(None);
+
+        let is_path = match kind {
+            Some(kind) => kind == "path",
+            None => url.scheme() == "file",
+        };
+        if is_path {
+            if url.scheme() != "file" {
+                anyhow::bail!(
+                    "The package ID specification `{spec}` refers to a path, but only `file://` URLs can"
+                );
+            }
+            let path = url.to_file_path().map_err(|()| {
+                anyhow::format_err!("Invalid path in package ID specification `{spec}`")
+            })?;
+            parsed.path = Some(path);
+        } else {
+            if parsed.name.is_none() {
+                let name = url
+                    .path_segments()
+                    .and_then(|segments| segments.filter(|s| !s.is_empty()).last())
+                    .ok_or_else(|| {
+                        anyhow::format_err!(
+                            "Missing package name in package ID specification `{spec}`"
+                        )
+                    })?;
+                parsed.name = Some(name.to_owned());
+            }
+            parsed.source = Some(SourceSpec {
+                kind: kind.map(str::to_owned),
+                url,
+            });
+        }
+        Ok(parsed)
+    }
+
+    /// Parse `name`, `name@version`, or the older `name:version`
+    fn parse_name_version(name_version: &str, spec: &str) -> CargoResult<Self> {
+        let (name, version) = match name_version.split_once(['@', ':']) {
+            Some((name, version)) => (name, Some(parse_partial_version(version, spec)?)),
+            None => (name_version, None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("Missing package name in package ID specification `{spec}`");
+        }
+        Ok(Self {
+            name: Some(name.to_owned()),
+            version,
+            ..Default::default()
+        })
+    }
+
+    fn matches(&self, package: &Package) -> bool {
+        let name_matches = self
+            .name
+            .as_deref()
+            .map(|name| name == package.name)
+            .unwrap_or(true);
+        let version_matches = self
+            .version
+            .as_deref()
+            .map(|version| matches_partial_version(version, &package.version))
+            .unwrap_or(true);
+        let path_matches = self
+            .path
+            .as_deref()
+            .map(|path| {
+                package
+                    .manifest_path
+                    .parent()
+                    .map(|dir| same_path(dir.as_std_path(), path))
+                    .unwrap_or(false)
+            })
+            .unwrap_or(true);
+        let source_matches = match &self.source {
+            Some(source) => package
+                .source
+                .as_ref()
+                .map_or(false, |actual| source.matches(&actual.repr)),
+            // Like cargo, a spec without a source matches packages from anywhere
+            None => true,
+        };
+        name_matches && version_matches && path_matches && source_matches
+    }
+}
+
+impl SourceSpec {
+    /// Whether the source `repr` of `cargo metadata`, like `git+https://...?branch=main#<rev>`,
+    /// is this one
+    ///
+    /// The revision of a git source is ignored, and so is its branch or tag unless it is given.
+    fn matches(&self, repr: &str) -> bool {
+        let (kind, url) = match repr.split_once('+') {
+            Some(source) => source,
+            None => return false,
+        };
+        if self
+            .kind
+            .as_deref()
+            .map_or(false, |expected| expected != kind)
+        {
+            return false;
+        }
+        let mut url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+        url.set_fragment(None);
+        if self.url.query().is_none() {
+            url.set_query(None);
+        }
+        url == self.url
+    }
+}
+
+/// Check that `version` is a full version or a prefix of one, like `1` or `1.2`
+fn parse_partial_version(version: &str, spec: &str) -> CargoResult<String> {
+    let is_partial = || {
+        let parts = version.split('.').collect::<Vec<_>>();
+        parts.len() < 3 && parts.iter().all(|part| part.parse::<u64>().is_ok())
+    };
+    if semver::Version::parse(version).is_err() && !is_partial() {
+        anyhow::bail!("Invalid version `{version}` in package ID specification `{spec}`");
+    }
+    Ok(version.to_owned())
+}
+
+fn matches_partial_version(partial: &str, version: &semver::Version) -> bool {
+    if let Ok(full) = semver::Version::parse(partial) {
+        return &full == version;
+    }
+    let parts = partial
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Vec<_>>();
+    match parts.as_slice() {
+        [Some(major)] => *major == version.major,
+        [Some(major), Some(minor)] => *major == version.major && *minor == version.minor,
+        _ => false,
+    }
+}
+
+fn same_path(lhs: &Path, rhs: &Path) -> bool {
+    match (dunce::canonicalize(lhs), dunce::canonicalize(rhs)) {
+        (Ok(lhs), Ok(rhs)) => lhs == rhs,
+        _ => lhs == rhs,
+    }
 }
 
 /// Search for Cargo.toml in this directory and recursively up the tree until one is found.
+///
+/// Like cargo, the nearest manifest wins, even if its package is excluded from the workspace
+/// further up.
 pub(crate) fn find_manifest_path(dir: &Path) -> CargoResult<std::path::PathBuf> {
     const MANIFEST_FILENAME: &str = "Cargo.toml";
+    let mut tried = Vec::new();
     for path in dir.ancestors() {
         let manifest = path.join(MANIFEST_FILENAME);
-        if std::fs::metadata(&manifest).is_ok() {
+        trace!("looking for {}", manifest.display());
+        if manifest.is_file() {
+            debug!("found manifest {}", manifest.display());
             return Ok(manifest);
         }
+        tried.push(manifest);
+    }
+    Err(ErrorKind::ManifestNotFound.attach(anyhow::format_err!(
+        "Unable to find Cargo.toml for {}, tried:\n{}",
+        dir.display(),
+        tried_paths(&tried)
+    )))
+}
+
+/// `paths` that were looked for, one per line
+pub(crate) fn tried_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("  {}", path.display()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(version: &str) -> semver::Version {
+        semver::Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn parse_name_version() {
+        let cwd = Path::new("/ws");
+        assert_eq!(
+            PkgIdSpec::parse("proto", cwd).unwrap(),
+            PkgIdSpec {
+                name: Some("proto".to_owned()),
+                ..Default::default()
+            }
+        );
+        for spec in ["proto@1.2", "proto:1.2"] {
+            assert_eq!(
+                PkgIdSpec::parse(spec, cwd).unwrap(),
+                PkgIdSpec {
+                    name: Some("proto".to_owned()),
+                    version: Some("1.2".to_owned()),
+                    ..Default::default()
+                }
+            );
+        }
+        assert!(PkgIdSpec::parse("proto@latest", cwd).is_err());
+        assert!(PkgIdSpec::parse("@1.0.0", cwd).is_err());
+    }
+
+    #[test]
+    fn resolution_errors() {
+        let failed = |stderr: &str| cargo_metadata::Error::CargoMetadata {
+            stderr: stderr.to_owned(),
+        };
+        assert!(is_resolution_error(&failed(
+            "error: failed to select a version for the requirement `serde = \"^99\"`"
+        )));
+        assert!(!is_resolution_error(&failed(
+            "error: the lock file /ws/Cargo.lock needs to be updated but --locked was passed to prevent this"
+        )));
+        assert!(!is_resolution_error(&failed(
+            "error: failed to parse manifest at `/ws/Cargo.toml`"
+        )));
+    }
+
+    #[test]
+    fn parse_path() {
+        let cwd = Path::new("/ws");
+        assert_eq!(
+            PkgIdSpec::parse("path:crates/proto", cwd).unwrap(),
+            PkgIdSpec {
+                path: Some(cwd.join("crates/proto")),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_url() {
+        let cwd = Path::new("/ws");
+        let dir = std::env::temp_dir().join("proto");
+        let url = Url::from_directory_path(&dir).unwrap();
+        let url = url.as_str().trim_end_matches('/');
+
+        let spec = PkgIdSpec::parse(&format!("{url}#proto@0.1.0"), cwd).unwrap();
+        assert_eq!(spec.name.as_deref(), Some("proto"));
+        assert_eq!(spec.version.as_deref(), Some("0.1.0"));
+        assert_eq!(spec.path, Some(dir.clone()));
+
+        let spec = PkgIdSpec::parse(&format!("path+{url}#0.1"), cwd).unwrap();
+        assert_eq!(spec.name, None);
+        assert_eq!(spec.version.as_deref(), Some("0.1"));
+        assert_eq!(spec.path, Some(dir));
+
+        assert!(PkgIdSpec::parse("path+https://github.com/foo/bar#bar", cwd).is_err());
+        assert!(PkgIdSpec::parse("svn+https://github.com/foo/bar#bar", cwd).is_err());
+    }
+
+    #[test]
+    fn parse_source() {
+        let cwd = Path::new("/ws");
+        let spec = PkgIdSpec::parse(
+            "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.152",
+            cwd,
+        )
+        .unwrap();
+        assert_eq!(spec.name.as_deref(), Some("serde"));
+        assert_eq!(spec.version.as_deref(), Some("1.0.152"));
+        assert_eq!(spec.path, None);
+        let source = spec.source.unwrap();
+        assert_eq!(source.kind.as_deref(), Some("registry"));
+        assert_eq!(
+            source.url.as_str(),
+            "https://github.com/rust-lang/crates.io-index"
+        );
+
+        // Named after the last segment
+        let spec = PkgIdSpec::parse("https://github.com/foo/bar#0.1", cwd).unwrap();
+        assert_eq!(spec.name.as_deref(), Some("bar"));
+        assert_eq!(spec.version.as_deref(), Some("0.1"));
+        assert_eq!(spec.source.unwrap().kind, None);
+    }
+
+    #[test]
+    fn sources_match() {
+        let cwd = Path::new("/ws");
+        let source = |spec: &str| PkgIdSpec::parse(spec, cwd).unwrap().source.unwrap();
+
+        let git = source("git+https://github.com/foo/bar#bar");
+        assert!(git.matches("git+https://github.com/foo/bar?branch=main#0123abcd"));
+        assert!(git.matches("git+https://github.com/foo/bar#0123abcd"));
+        assert!(!git.matches("registry+https://github.com/foo/bar"));
+        assert!(!git.matches("git+https://github.com/foo/baz#0123abcd"));
+
+        let branch = source("git+https://github.com/foo/bar?branch=dev#bar");
+        assert!(branch.matches("git+https://github.com/foo/bar?branch=dev#0123abcd"));
+        assert!(!branch.matches("git+https://github.com/foo/bar?branch=main#0123abcd"));
+
+        let any = source("https://github.com/rust-lang/crates.io-index#serde");
+        assert!(any.matches("registry+https://github.com/rust-lang/crates.io-index"));
+    }
+
+    fn member(name: &str, dir: &str, publish: Option<Vec<String>>) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": "0.1.0",
+            "id": format!("{name} 0.1.0 (path+file:///ws/{dir})"),
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": Path::new("/ws").join(dir).join("Cargo.toml"),
+            "publish": publish,
+        }))
+        .unwrap()
+    }
+
+    fn names(packages: &[&Package]) -> Vec<String> {
+        packages.iter().map(|p| p.name.clone()).collect()
+    }
+
+    #[test]
+    fn filter_members_with_reasons() {
+        let members = [
+            member("root", "", None),
+            member("net-http", "crates/net-http", None),
+            member("net-dns", "crates/net-dns", Some(vec![])),
+            member("xtask", "xtask", Some(vec![])),
+        ];
+        let root = Path::new("/ws");
+        let default_members = ["crates/*".to_owned(), ".".to_owned()];
+
+        let filter = MemberFilter {
+            exclude: vec!["net-h*".to_owned()],
+            only_publishable: true,
+            respect_default_members: true,
+            ..Default::default()
+        };
+        let selection =
+            filter_members(&members, root, Some(&default_members[..]), &filter).unwrap();
+        assert_eq!(names(&selection.selected), ["root"]);
+        let skipped = selection
+            .skipped
+            .iter()
+            .map(|(p, reason)| (p.name.as_str(), *reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            skipped,
+            [
+                ("net-http", SkipReason::Excluded),
+                ("net-dns", SkipReason::NotPublishable),
+                ("xtask", SkipReason::NotDefaultMember),
+            ]
+        );
+        assert!(selection.is_skipped_for(&members[1], SkipReason::Excluded));
+
+        // Naming members overrides the default ones
+        let filter = MemberFilter {
+            include: vec!["xtask".to_owned()],
+            respect_default_members: true,
+            ..Default::default()
+        };
+        let selection =
+            filter_members(&members, root, Some(&default_members[..]), &filter).unwrap();
+        assert_eq!(names(&selection.selected), ["xtask"]);
+        assert!(selection
+            .skipped
+            .iter()
+            .all(|(_, reason)| *reason == SkipReason::NotIncluded));
+
+        let selection = filter_members(
+            &members,
+            root,
+            Some(&default_members[..]),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(selection.selected.len(), members.len());
+    }
+
+    #[test]
+    fn globs() {
+        assert!(matches_glob("net-*", "net-http"));
+        assert!(matches_glob("net-*", "net-"));
+        assert!(matches_glob("*-http", "net-http"));
+        assert!(matches_glob("n?t-*p", "net-http"));
+        assert!(matches_glob("*", "net"));
+        assert!(!matches_glob("net-*", "netlink"));
+        assert!(!matches_glob("n?t", "nt"));
+        assert!(matches_glob("crates/net-*", "crates/net-http"));
+        assert!(!matches_glob("crates/*", "crates/net/http"));
+        assert!(!matches_glob("*", "crates/net"));
+        assert!(matches_glob("*/*", "crates/net"));
+    }
+
+    #[test]
+    fn partial_versions() {
+        assert!(matches_partial_version("1", &version("1.2.3")));
+        assert!(matches_partial_version("1.2", &version("1.2.3")));
+        assert!(matches_partial_version("1.2.3", &version("1.2.3")));
+        assert!(!matches_partial_version("1.3", &version("1.2.3")));
+        assert!(!matches_partial_version("1.2.3", &version("1.2.3-alpha.1")));
     }
-    anyhow::bail!("Unable to find Cargo.toml for {}", dir.display());
 }
@@ -0,0 +1,57 @@
+//! Operations behind the cargo-edit commands, for use without a terminal
+//!
+//! Options are plain structs and nothing is printed: every edit is returned in a report, and can
+//! also be observed while it is being made, e.g. to show progress.
+
+mod rm;
+mod set_version;
+mod upgrade;
+
+pub use crate::{BumpLevel, TargetVersion};
+pub use rm::{
+    rm, rm_with, CheckUsage, LockfileUpdateFailed, RmChange, RmChangeKind, RmEvent, RmOptions,
+    RmReport,
+};
+pub use set_version::{
+    set_version, set_version_in, set_version_with, SetVersionOptions, SetVersionReport,
+};
+pub use upgrade::{
+    upgrade, upgrade_with, DependencyUpgrade, UpgradeEvent, UpgradeOptions, UpgradeReason,
+    UpgradeReport,
+};
+
+use std::path::PathBuf;
+
+/// Edit made to a manifest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestChange {
+    /// Workspace member owning the manifest, or `workspace` for a virtual manifest
+    pub package: String,
+    /// Manifest that was edited
+    pub manifest_path: PathBuf,
+    /// What was edited
+    pub kind: ChangeKind,
+    /// Value before the edit
+    pub old: String,
+    /// Value after the edit
+    pub new: String,
+}
+
+/// Field of the manifest a [`ManifestChange`] edited
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// `package.version`
+    PackageVersion,
+    /// `workspace.package.version`
+    WorkspaceVersion,
+    /// The package's version was changed through `workspace.package.version`, so its manifest
+    /// was left as is
+    InheritedPackageVersion,
+    /// Version requirement of the dependency on `dependency`
+    DependencyRequirement {
+        /// Path of the dependency table, e.g. `["target", "cfg(unix)", "dependencies"]`
+        table: Vec<String>,
+        /// Key of the dependency
+        dependency: String,
+    },
+}
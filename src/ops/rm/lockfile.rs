@@ -0,0 +1,92 @@
+//! Keeping `Cargo.lock` in sync with the edited manifests.
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use super::restore;
+use crate::errors::*;
+
+/// `Cargo.lock` is out of date with the manifests that were written
+///
+/// Found in the error chain when [`super::rm`] wrote the manifests but `cargo update` failed.
+#[derive(Debug)]
+pub struct LockfileUpdateFailed {
+    /// How to update it by hand
+    pub command: String,
+}
+
+impl fmt::Display for LockfileUpdateFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the manifest was updated but `Cargo.lock` wasn't, run `{}` to update it",
+            self.command
+        )
+    }
+}
+
+/// Re-resolve the workspace of `manifest_path`, dropping packages that are no longer needed
+///
+/// Only the workspace members are updated, everything else stays at its locked version.  Does
+/// nothing if the workspace has no `Cargo.lock`.  With `locked`, this fails if `Cargo.lock` would
+/// change.
+pub(crate) fn update(manifest_path: &Path, locked: bool, offline: bool) -> CargoResult<()> {
+    let ws = match cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .exec()
+    {
+        Ok(ws) => ws,
+        // Without the workspace, only a lockfile right next to the manifest is known to exist
+        Err(err) => {
+            let lockfile = manifest_path.with_file_name("Cargo.lock");
+            if lockfile.exists() {
+                return Err(failed(manifest_path, err.into()));
+            }
+            return Ok(());
+        }
+    };
+    let root_manifest = ws.workspace_root.join("Cargo.toml").into_std_path_buf();
+    if !ws.workspace_root.join("Cargo.lock").exists() {
+        return Ok(());
+    }
+
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut cmd = Command::new(cargo);
+    cmd.arg("update")
+        .arg("--workspace")
+        .arg("--manifest-path")
+        .arg(&root_manifest);
+    if locked {
+        cmd.arg("--locked");
+    }
+    if offline {
+        cmd.arg("--offline");
+    }
+    match cmd.output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(failed(
+                &root_manifest,
+                anyhow::format_err!("{}", stderr.trim_end()),
+            ))
+        }
+        Err(err) => Err(failed(
+            &root_manifest,
+            anyhow::Error::new(err).context("Failed to run `cargo update`"),
+        )),
+    }
+}
+
+/// Wrap `err` in a [`LockfileUpdateFailed`] suggesting to update the lockfile of `manifest_path`
+fn failed(manifest_path: &Path, err: anyhow::Error) -> anyhow::Error {
+    let mut command = "cargo update --workspace".to_owned();
+    if !restore::is_default_manifest(manifest_path) {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let manifest_path = restore::relative_path(manifest_path, &cwd);
+        command.push_str(" --manifest-path ");
+        command.push_str(&restore::quote(&manifest_path));
+    }
+    err.context(LockfileUpdateFailed { command })
+}
@@ -0,0 +1,1049 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::{
+    find, manifest_from_pkgid, Dependency, EditConfig, FeatureChange, LocalManifest, MemberFilter,
+    WorkspaceMetadata,
+};
+
+mod lockfile;
+mod restore;
+mod target;
+mod unused;
+mod usage;
+
+pub use lockfile::LockfileUpdateFailed;
+use target::{target_matches, TargetCfg};
+
+/// What [`rm`] should do
+#[derive(Clone, Debug, Default)]
+pub struct RmOptions {
+    /// Dependencies to remove, by key or by the name of the package they are on
+    pub dependencies: Vec<String>,
+    /// Manifest to remove from, instead of the one found from the current directory
+    pub manifest_path: Option<PathBuf>,
+    /// Package to remove from, as a package ID specification
+    pub package: Option<String>,
+    /// Remove from all packages in the workspace
+    pub workspace: bool,
+    /// Packages to leave untouched with `workspace`
+    pub exclude: Vec<String>,
+    /// Only remove from development dependencies
+    pub dev: bool,
+    /// Only remove from build dependencies
+    pub build: bool,
+    /// Only remove from dependencies for this target platform, a triple or a `cfg(...)`
+    pub target: Option<String>,
+    /// Remove these features from the dependency instead of the dependency
+    pub features: Vec<String>,
+    /// Remove the dependencies that `cargo check` reports as unused, instead of `dependencies`
+    pub unused: bool,
+    /// Also remove the dependency from members inheriting it from `[workspace.dependencies]`
+    pub recursive: bool,
+    /// Remove every renamed dependency on the package, if there are several
+    pub all_matching: bool,
+    /// Keep `[patch]` and `[replace]` entries for dependencies that are no longer used
+    pub keep_patches: bool,
+    /// Remove optional dependencies even if features enable them, editing those features
+    pub force: bool,
+    /// Look for uses of the dependency in the package's source before removing it
+    pub check_usage: Option<CheckUsage>,
+    /// Whether to update `Cargo.lock` afterwards
+    ///
+    /// The `update-lockfile` setting is used when `None`, updating it unless turned off.
+    pub update_lockfile: Option<bool>,
+    /// Ignore cargo-edit's workspace and user configuration
+    pub no_config: bool,
+    /// Report the removals without writing them
+    pub dry_run: bool,
+    /// Run without accessing the network
+    pub offline: bool,
+    /// Require `Cargo.lock` to be up to date
+    pub locked: bool,
+}
+
+/// What to do with a dependency that appears to still be used, see [`RmOptions::check_usage`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CheckUsage {
+    /// Report uses, but remove the dependency anyway
+    Warn,
+    /// Don't remove a dependency that is still used
+    Error,
+}
+
+/// Edits made by [`rm`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RmReport {
+    /// Every edit, in the order it was made
+    pub changes: Vec<RmChange>,
+}
+
+/// Edit made to a manifest by [`rm`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RmChange {
+    /// Workspace member owning the manifest, when removing from more than the one manifest
+    pub member: Option<String>,
+    /// Manifest that was edited
+    pub manifest_path: PathBuf,
+    /// What was edited
+    pub kind: RmChangeKind,
+}
+
+/// What an [`RmChange`] removed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RmChangeKind {
+    /// A dependency
+    Dependency {
+        /// Path of the dependency table, e.g. `["target", "cfg(unix)", "dependencies"]`
+        table: Vec<String>,
+        /// Key of the dependency
+        key: String,
+        /// Name the dependency was asked for by, the package it is on when `key` is a rename
+        name: String,
+        /// Whether the member inherited the dependency from a `[workspace.dependencies]` entry
+        /// that is removed too
+        inherited: bool,
+        /// The removed entry, unless it couldn't be read
+        entry: Option<Dependency>,
+        /// `cargo add` command adding the entry back, unless it was in
+        /// `[workspace.dependencies]`
+        restore: Option<String>,
+    },
+    /// A feature activated on a dependency, see [`RmOptions::features`]
+    DependencyFeature {
+        /// Path of the dependency table
+        table: Vec<String>,
+        /// Key of the dependency
+        key: String,
+        /// Feature that is no longer activated
+        feature: String,
+    },
+    /// An edit to `[features]`, as the removed dependency can't be activated anymore
+    Features(FeatureChange),
+    /// A `[patch]` or `[replace]` entry for a package nothing depends on anymore
+    Patch {
+        /// Path of the table, e.g. `["patch", "crates-io"]`
+        table: Vec<String>,
+        /// Key of the entry
+        key: String,
+    },
+}
+
+/// Progress of [`rm_with`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RmEvent {
+    /// The `cfg` values of the target triple aren't known, so only `[target]` tables named after
+    /// the triple are matched
+    TargetCfgUnavailable {
+        /// Target triple, from [`RmOptions::target`]
+        target: String,
+        /// Why the `cfg` values aren't known
+        error: String,
+    },
+    /// An edit was made
+    Changed(RmChange),
+    /// The dependency appears to still be used, see [`RmOptions::check_usage`]
+    StillUsed {
+        /// Key of the dependency
+        key: String,
+        /// File, relative to the package root
+        path: PathBuf,
+        /// 1-based line number
+        line: usize,
+    },
+    /// A feature no longer enables everything it did before the removal
+    FeatureNarrowed {
+        /// Workspace member owning the feature, when removing from more than the one manifest
+        member: Option<String>,
+        /// Feature that changed
+        feature: String,
+        /// What the feature no longer enables
+        lost: Vec<String>,
+    },
+    /// A workspace member doesn't have one of the dependencies, with [`RmOptions::workspace`]
+    NotADependency {
+        /// Dependency, as given in [`RmOptions::dependencies`]
+        name: String,
+        /// Member without the dependency
+        member: String,
+    },
+}
+
+/// Remove dependencies from manifests, and update `Cargo.lock`
+///
+/// The manifests are only written once every removal succeeded.  If they were written but
+/// `Cargo.lock` couldn't be updated, the error has a [`LockfileUpdateFailed`] in its chain.
+pub fn rm(options: &RmOptions) -> CargoResult<RmReport> {
+    rm_with(options, |_| Ok(()))
+}
+
+/// Like [`rm`], with `on_event` called as the removal progresses
+///
+/// An error from `on_event` aborts the operation.
+pub fn rm_with(
+    options: &RmOptions,
+    mut on_event: impl FnMut(&RmEvent) -> CargoResult<()>,
+) -> CargoResult<RmReport> {
+    if !options.features.is_empty() && options.dependencies.len() > 1 {
+        anyhow::bail!("`--features` can only be used with a single dependency");
+    }
+    let config = if options.no_config {
+        EditConfig::default()
+    } else {
+        EditConfig::load(options.manifest_path.as_deref())?
+    };
+    let update_lockfile = match options.update_lockfile {
+        Some(update_lockfile) => update_lockfile,
+        None => config.update_lockfile.unwrap_or(true),
+    };
+    let target_cfg = match options.target.as_deref() {
+        Some(target) => match TargetCfg::new(target) {
+            Ok(target_cfg) => target_cfg,
+            Err(err) => {
+                on_event(&RmEvent::TargetCfgUnavailable {
+                    target: target.trim().to_owned(),
+                    error: format!("{err:#}"),
+                })?;
+                TargetCfg::default()
+            }
+        },
+        None => TargetCfg::default(),
+    };
+
+    let mut rm = Rm {
+        options,
+        target_cfg,
+        on_event: &mut on_event,
+        report: RmReport::default(),
+    };
+    if options.workspace {
+        rm.exec_workspace(update_lockfile)?;
+    } else {
+        rm.exec(update_lockfile)?;
+    }
+    Ok(rm.report)
+}
+
+const WORKSPACE_DEPENDENCIES: &[&str] = &["workspace", "dependencies"];
+
+/// Workspace member whose manifest may need editing too
+struct Member {
+    name: String,
+    manifest: LocalManifest,
+    /// This is the (possibly virtual) manifest of the workspace root
+    is_root: bool,
+    /// Left untouched, having been skipped by `--exclude`
+    skipped: bool,
+    modified: bool,
+}
+
+/// State of an [`rm_with`] run
+struct Rm<'a> {
+    options: &'a RmOptions,
+    /// `cfg` values of `options.target`, for matching the `cfg(...)` tables that apply to it
+    target_cfg: TargetCfg,
+    on_event: &'a mut dyn FnMut(&RmEvent) -> CargoResult<()>,
+    report: RmReport,
+}
+
+impl Rm<'_> {
+    fn exec(&mut self, update_lockfile: bool) -> CargoResult<()> {
+        let options = self.options;
+        let manifest_path = if let Some(ref pkgid) = options.package {
+            let pkg = manifest_from_pkgid(options.manifest_path.as_deref(), pkgid)?;
+            Cow::Owned(Some(pkg.manifest_path.into_std_path_buf()))
+        } else {
+            Cow::Borrowed(&options.manifest_path)
+        };
+        let mut manifest = LocalManifest::find(manifest_path.as_deref())?;
+        let unused;
+        let deps = if options.unused {
+            unused = self.find_unused(&manifest)?;
+            if unused.is_empty() {
+                return Ok(());
+            }
+            &unused
+        } else {
+            &options.dependencies
+        };
+        let mut members: Option<Vec<Member>> = None;
+
+        for name in deps {
+            for dep in self.resolve_dependency_keys(&manifest, name)? {
+                self.remove_dependency(&mut manifest, &mut members, &dep, name, None)?;
+            }
+        }
+
+        if !options.dry_run {
+            manifest.write()?;
+            for member in members.iter().flatten().filter(|member| member.modified) {
+                member.manifest.write()?;
+            }
+            if update_lockfile {
+                lockfile::update(&manifest.path, options.locked, options.offline)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dependencies selected by the options that the compiler reports as unused
+    fn find_unused(&self, manifest: &LocalManifest) -> CargoResult<Vec<String>> {
+        let mut unused = unused::find_unused(manifest, self.options.locked, self.options.offline)?;
+        unused.retain(|dep| !self.dependency_sections(manifest, dep).is_empty());
+        Ok(unused)
+    }
+
+    /// Remove the dependencies from every workspace member not in `--exclude`
+    ///
+    /// All manifests are loaded before anything is removed and written only once every member
+    /// has been handled, so an error leaves the workspace untouched.
+    fn exec_workspace(&mut self, update_lockfile: bool) -> CargoResult<()> {
+        let options = self.options;
+        let filter = MemberFilter {
+            exclude: options.exclude.clone(),
+            ..Default::default()
+        };
+        let mut members = load_workspace(&find(options.manifest_path.as_deref())?, &filter)?;
+        // The root goes last, so its `[workspace.dependencies]` are no longer inherited by then
+        members.sort_by_key(|member| member.is_root);
+
+        let mut found = vec![false; options.dependencies.len()];
+        for idx in 0..members.len() {
+            if members[idx].skipped {
+                continue;
+            }
+            let mut member = members.remove(idx);
+            let mut others = Some(members);
+            let result = self.remove_from_member(&mut member, &mut others, &mut found);
+            members = others.expect("never unloaded");
+            members.insert(idx, member);
+            result?;
+        }
+
+        for (name, found) in options.dependencies.iter().zip(found) {
+            if !found {
+                return Err(ErrorKind::NothingToDo.attach(anyhow::format_err!(
+                    "The dependency `{name}` could not be found in any workspace member."
+                )));
+            }
+        }
+
+        if !options.dry_run {
+            for member in members.iter().filter(|member| member.modified) {
+                member.manifest.write()?;
+            }
+            if update_lockfile {
+                lockfile::update(&members[0].manifest.path, options.locked, options.offline)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the dependencies from `member`, noting those it doesn't have
+    fn remove_from_member(
+        &mut self,
+        member: &mut Member,
+        others: &mut Option<Vec<Member>>,
+        found: &mut [bool],
+    ) -> CargoResult<()> {
+        let options = self.options;
+        // A virtual root only has `[workspace.dependencies]`, which aren't worth a note
+        let is_package = member.manifest.data.contains_key("package");
+        for (name, found) in options.dependencies.iter().zip(found) {
+            let mut present = false;
+            for dep in self.resolve_dependency_keys(&member.manifest, name)? {
+                if self.dependency_sections(&member.manifest, &dep).is_empty() {
+                    continue;
+                }
+                self.remove_dependency(
+                    &mut member.manifest,
+                    others,
+                    &dep,
+                    name,
+                    Some(&member.name),
+                )?;
+                present = true;
+            }
+            if present {
+                member.modified = true;
+                *found = true;
+            } else if is_package {
+                self.emit(RmEvent::NotADependency {
+                    name: name.clone(),
+                    member: member.name.clone(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Dependency table to report as missing the dependency, for the section options
+    fn section(&self) -> Vec<String> {
+        let options = self.options;
+        let section_name = if options.dev {
+            "dev-dependencies"
+        } else if options.build {
+            "build-dependencies"
+        } else {
+            "dependencies"
+        };
+
+        if let Some(ref target) = options.target {
+            assert!(!target.is_empty(), "Target specification may not be empty");
+
+            vec!["target".to_owned(), target.clone(), section_name.to_owned()]
+        } else {
+            vec![section_name.to_owned()]
+        }
+    }
+
+    /// Whether the user restricted which dependency tables to look in
+    fn has_section_filter(&self) -> bool {
+        self.options.dev || self.options.build || self.options.target.is_some()
+    }
+
+    /// Whether the dependency table at `path` passes the section filters
+    fn matches_section(&self, path: &[String]) -> bool {
+        let options = self.options;
+        let kind = path.last().map(String::as_str);
+        let kind_matches = if options.dev {
+            kind == Some("dev-dependencies")
+        } else if options.build {
+            kind == Some("build-dependencies")
+        } else {
+            true
+        };
+        let target_matches = match &options.target {
+            Some(target) => path.len() >= 3 && target_matches(&path[1], target, &self.target_cfg),
+            None => true,
+        };
+        kind_matches && target_matches
+    }
+
+    /// Dependency tables `dep` is in, as selected by the section options
+    fn dependency_sections(&self, manifest: &LocalManifest, dep: &str) -> Vec<Vec<String>> {
+        let mut sections = manifest.get_dependency_table_paths(dep);
+        sections.retain(|section| self.matches_section(section));
+        let in_workspace_table = manifest
+            .get_workspace_dependency_table()
+            .map(|table| table.contains_key(dep))
+            .unwrap_or(false);
+        if in_workspace_table && !self.has_section_filter() {
+            sections.push(
+                WORKSPACE_DEPENDENCIES
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            );
+        }
+        sections
+    }
+
+    /// Remove the dependency with the key `dep`, which was asked for as `name`
+    ///
+    /// `owner` names the workspace member `manifest` belongs to, when removing from several.
+    fn remove_dependency(
+        &mut self,
+        manifest: &mut LocalManifest,
+        members: &mut Option<Vec<Member>>,
+        dep: &str,
+        name: &str,
+        owner: Option<&str>,
+    ) -> CargoResult<()> {
+        let options = self.options;
+        let mut sections = self.dependency_sections(manifest, dep);
+        if sections.is_empty() {
+            if !self.has_section_filter() {
+                return Err(ErrorKind::NothingToDo.attach(anyhow::format_err!(
+                    "The dependency `{dep}` could not be found in any dependency table."
+                )));
+            }
+            if let Some(target) = &options.target {
+                let targets = manifest
+                    .get_dependency_table_paths(dep)
+                    .into_iter()
+                    .filter(|section| section.len() >= 3)
+                    .map(|section| format!("`{}`", section[1]))
+                    .collect::<Vec<_>>();
+                if !targets.is_empty() {
+                    return Err(ErrorKind::NothingToDo.attach(anyhow::format_err!(
+                        "The dependency `{dep}` could not be found for target `{target}`, it is only present for {}.",
+                        targets.join(", ")
+                    )));
+                }
+            }
+            // Report the missing table or dependency for the requested section
+            sections.push(self.section());
+        }
+
+        if !options.features.is_empty() {
+            return self.remove_features(manifest, &sections, dep, owner);
+        }
+
+        // Members can't inherit a dependency that no longer exists
+        let mut inheriting = Vec::new();
+        if sections
+            .iter()
+            .any(|section| section == WORKSPACE_DEPENDENCIES)
+        {
+            if members.is_none() {
+                *members = Some(load_ws_members(manifest)?);
+            }
+            let members = members.as_ref().expect("just loaded");
+            for (idx, member) in members.iter().enumerate() {
+                let member_sections = member
+                    .manifest
+                    .get_dependency_table_paths(dep)
+                    .into_iter()
+                    .filter(|section| is_inherited(&member.manifest, section, dep))
+                    .collect::<Vec<_>>();
+                if !member_sections.is_empty() {
+                    inheriting.push((idx, member_sections));
+                }
+            }
+            if !inheriting.is_empty() && !options.recursive {
+                let names = inheriting
+                    .iter()
+                    .map(|(idx, _)| format!("`{}`", members[*idx].name))
+                    .collect::<Vec<_>>();
+                anyhow::bail!(
+                    "The dependency `{dep}` is inherited from `[workspace.dependencies]` by {}; pass `--recursive` to remove it from those members too.",
+                    names.join(", ")
+                );
+            }
+        }
+
+        if let Some(check_usage) = options.check_usage {
+            let uses = usage::find_uses(manifest, dep)?;
+            for found in &uses {
+                self.emit(RmEvent::StillUsed {
+                    key: dep.to_owned(),
+                    path: found.path.clone(),
+                    line: found.line,
+                })?;
+            }
+            if !uses.is_empty() && check_usage == CheckUsage::Error {
+                anyhow::bail!(
+                    "The dependency `{dep}` appears to still be used, so it was not removed."
+                );
+            }
+        }
+
+        let expansions = self.check_feature_references(manifest, &sections, dep, owner)?;
+        let mut member_expansions = Vec::new();
+        for (idx, member_sections) in &inheriting {
+            let member = &members.as_ref().expect("loaded along with `inheriting`")[*idx];
+            member_expansions.push(self.check_feature_references(
+                &member.manifest,
+                member_sections,
+                dep,
+                Some(&member.name),
+            )?);
+        }
+
+        let package = get_dependency(manifest, &sections[0], dep)
+            .and_then(|item| item.get("package"))
+            .and_then(|package| package.as_str())
+            .unwrap_or(dep)
+            .to_owned();
+
+        for section in sections {
+            self.record_removal(manifest, &section, dep, name, owner, false)?;
+            manifest.remove_from_table(&section, dep)?;
+        }
+
+        // Now that we have removed the crate, if that was the last reference to that crate,
+        // then we need to drop any explicitly activated features on that crate.
+        for change in manifest.gc_dep(dep) {
+            self.change(owner, &manifest.path, RmChangeKind::Features(change))?;
+        }
+        if let Some(expansions) = expansions {
+            self.report_expansion_changes(manifest, &expansions, owner)?;
+        }
+
+        for ((idx, member_sections), expansions) in inheriting.into_iter().zip(member_expansions) {
+            let member = &mut members.as_mut().expect("loaded along with `inheriting`")[idx];
+            for section in member_sections {
+                self.record_removal(
+                    &member.manifest,
+                    &section,
+                    dep,
+                    name,
+                    Some(&member.name),
+                    true,
+                )?;
+                member.manifest.remove_from_table(&section, dep)?;
+            }
+            for change in member.manifest.gc_dep(dep) {
+                self.change(
+                    Some(&member.name),
+                    &member.manifest.path,
+                    RmChangeKind::Features(change),
+                )?;
+            }
+            if let Some(expansions) = expansions {
+                self.report_expansion_changes(&member.manifest, &expansions, Some(&member.name))?;
+            }
+            member.modified = true;
+        }
+
+        if !options.keep_patches {
+            self.prune_patches(manifest, members, &package, owner)?;
+        }
+
+        Ok(())
+    }
+
+    /// Record the removal of the entry for `dep` in the table at `section`, before it is removed
+    fn record_removal(
+        &mut self,
+        manifest: &LocalManifest,
+        section: &[String],
+        dep: &str,
+        name: &str,
+        owner: Option<&str>,
+        inherited: bool,
+    ) -> CargoResult<()> {
+        let crate_root = manifest.path.parent().expect("manifest path is absolute");
+        // Not something we can describe, removing it is still fine
+        let entry = get_dependency(manifest, section, dep)
+            .and_then(|item| Dependency::from_toml(crate_root, dep, item).ok());
+        let restore = match &entry {
+            Some(dependency) if section != WORKSPACE_DEPENDENCIES => {
+                let cwd = std::env::current_dir()?;
+                let manifest_path = (!restore::is_default_manifest(&manifest.path))
+                    .then_some(manifest.path.as_path());
+                Some(restore::add_command(
+                    dependency,
+                    section,
+                    manifest_path,
+                    &cwd,
+                ))
+            }
+            _ => None,
+        };
+        self.change(
+            owner,
+            &manifest.path,
+            RmChangeKind::Dependency {
+                table: section.to_vec(),
+                key: dep.to_owned(),
+                name: name.to_owned(),
+                inherited,
+                entry,
+                restore,
+            },
+        )
+    }
+
+    /// Find the keys of the dependencies that `name` refers to
+    ///
+    /// This is `name` itself, unless only renamed dependencies on the package `name` exist.
+    fn resolve_dependency_keys(
+        &self,
+        manifest: &LocalManifest,
+        name: &str,
+    ) -> CargoResult<Vec<String>> {
+        let matches = |section: &[String]| {
+            if section == WORKSPACE_DEPENDENCIES {
+                !self.has_section_filter()
+            } else {
+                self.matches_section(section)
+            }
+        };
+        let mut keys = Vec::new();
+        for (section, key) in manifest.find_package_dependencies(name) {
+            if matches(&section) && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        if keys.is_empty() || keys.iter().any(|key| key == name) {
+            return Ok(vec![name.to_owned()]);
+        }
+        if keys.len() > 1 && !self.options.all_matching {
+            let keys = keys
+                .iter()
+                .map(|key| format!("`{key}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow::bail!(
+                "The package `{name}` is a dependency under several names: {keys}; pass `--all-matching` to remove all of them."
+            );
+        }
+        Ok(keys)
+    }
+
+    /// Remove the features of the options from `dep` wherever they are activated within
+    /// `sections`
+    fn remove_features(
+        &mut self,
+        manifest: &mut LocalManifest,
+        sections: &[Vec<String>],
+        dep: &str,
+        owner: Option<&str>,
+    ) -> CargoResult<()> {
+        let features = self.options.features.as_slice();
+        let mut removals = Vec::new();
+        for section in sections {
+            let removed = manifest.remove_dep_features(section, dep, features)?;
+            removals.push((section, removed));
+        }
+
+        let missing = features
+            .iter()
+            .filter(|feature| {
+                !removals
+                    .iter()
+                    .any(|(_, removed)| removed.contains(*feature))
+            })
+            .map(|feature| format!("`{feature}`"))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "The dependency `{dep}` does not activate the feature(s) {}.",
+                missing.join(", ")
+            );
+        }
+
+        for (section, removed) in removals {
+            for feature in removed {
+                self.change(
+                    owner,
+                    &manifest.path,
+                    RmChangeKind::DependencyFeature {
+                        table: section.clone(),
+                        key: dep.to_owned(),
+                        feature,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse to remove the optional dependency `dep` while features enable it, unless `--force`
+    ///
+    /// Returns what the features expand to before the removal if they are about to be edited,
+    /// for [`Rm::report_expansion_changes`].
+    fn check_feature_references(
+        &self,
+        manifest: &LocalManifest,
+        sections: &[Vec<String>],
+        dep: &str,
+        owner: Option<&str>,
+    ) -> CargoResult<Option<Vec<(String, BTreeSet<String>)>>> {
+        // Features only lose their references once the dependency is gone from every table
+        let remains = manifest
+            .get_dependency_table_paths(dep)
+            .iter()
+            .any(|section| !sections.contains(section));
+        let optional = sections
+            .iter()
+            .filter_map(|section| get_dependency(manifest, section, dep))
+            .any(|item| {
+                item.get("optional")
+                    .and_then(|optional| optional.as_bool())
+                    .unwrap_or(false)
+            });
+        if remains || !optional {
+            return Ok(None);
+        }
+        let chains = manifest.feature_references(dep);
+        if chains.is_empty() {
+            return Ok(None);
+        }
+
+        if !self.options.force {
+            let chains = chains
+                .iter()
+                .map(|chain| {
+                    let chain = chain
+                        .iter()
+                        .map(|link| format!("`{link}`"))
+                        .collect::<Vec<_>>();
+                    format!("  {}", chain.join(" -> "))
+                })
+                .collect::<Vec<_>>();
+            let owner = owner
+                .map(|owner| format!(" of `{owner}`"))
+                .unwrap_or_default();
+            anyhow::bail!(
+                "The optional dependency `{dep}`{owner} is enabled by features whose meaning would change without it; pass `--force` to remove it along with these references:\n{}",
+                chains.join("\n")
+            );
+        }
+        Ok(Some(manifest.feature_expansions()))
+    }
+
+    /// Report what the remaining features no longer enable, compared to `before`
+    ///
+    /// Removed features are already reported along with the edits to `[features]`.
+    fn report_expansion_changes(
+        &mut self,
+        manifest: &LocalManifest,
+        before: &[(String, BTreeSet<String>)],
+        owner: Option<&str>,
+    ) -> CargoResult<()> {
+        let after = manifest.feature_expansions();
+        for (feature, old) in before {
+            let new = match after.iter().find(|(name, _)| name == feature) {
+                Some((_, new)) => new,
+                None => continue,
+            };
+            let lost = old.difference(new).cloned().collect::<Vec<_>>();
+            if lost.is_empty() {
+                continue;
+            }
+            self.emit(RmEvent::FeatureNarrowed {
+                member: owner.map(str::to_owned),
+                feature: feature.clone(),
+                lost,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Remove `[patch]` and `[replace]` entries for `package` once nothing in the workspace
+    /// depends on it directly
+    fn prune_patches(
+        &mut self,
+        manifest: &mut LocalManifest,
+        members: &mut Option<Vec<Member>>,
+        package: &str,
+        owner: Option<&str>,
+    ) -> CargoResult<()> {
+        if !manifest.find_package_dependencies(package).is_empty() {
+            return Ok(());
+        }
+
+        // `[patch]` is only honored in the workspace root, so if `manifest` has some it is the
+        // root
+        let in_workspace = if !find_patches(manifest, package).is_empty() {
+            manifest.data.contains_key("workspace")
+        } else {
+            // Avoid querying the workspace unless its root has something to prune
+            let root_has_patches = find_ws_root_path(manifest)
+                .and_then(|root_path| LocalManifest::try_new(&root_path).ok())
+                .map(|root| !find_patches(&root, package).is_empty())
+                .unwrap_or(false);
+            if !root_has_patches {
+                return Ok(());
+            }
+            true
+        };
+
+        let (owner, root) = if in_workspace {
+            if members.is_none() {
+                *members = Some(load_ws_members(manifest)?);
+            }
+            let members = members.as_mut().expect("just loaded");
+            if members.iter().any(|member| {
+                !member
+                    .manifest
+                    .find_package_dependencies(package)
+                    .is_empty()
+            }) {
+                return Ok(());
+            }
+            match members.iter_mut().find(|member| member.is_root) {
+                Some(member) => {
+                    member.modified = true;
+                    (Some(member.name.as_str()), &mut member.manifest)
+                }
+                None => (owner, manifest),
+            }
+        } else {
+            (owner, manifest)
+        };
+
+        for (table, key) in find_patches(root, package) {
+            self.change(
+                owner,
+                &root.path,
+                RmChangeKind::Patch {
+                    table: table.clone(),
+                    key: key.clone(),
+                },
+            )?;
+            root.remove_from_table(&table, &key)?;
+        }
+        Ok(())
+    }
+
+    fn change(
+        &mut self,
+        member: Option<&str>,
+        manifest_path: &Path,
+        kind: RmChangeKind,
+    ) -> CargoResult<()> {
+        self.emit(RmEvent::Changed(RmChange {
+            member: member.map(str::to_owned),
+            manifest_path: manifest_path.to_owned(),
+            kind,
+        }))
+    }
+
+    fn emit(&mut self, event: RmEvent) -> CargoResult<()> {
+        (self.on_event)(&event)?;
+        if let RmEvent::Changed(change) = event {
+            self.report.changes.push(change);
+        }
+        Ok(())
+    }
+}
+
+/// Load the manifests of all other members of the workspace `current` is in
+fn load_ws_members(current: &LocalManifest) -> CargoResult<Vec<Member>> {
+    let mut members = load_workspace(&current.path, &MemberFilter::default())?;
+    members.retain(|member| member.manifest.path != current.path);
+    Ok(members)
+}
+
+/// Load the manifests of all members of the workspace `manifest_path` is in, sorted by name
+///
+/// Members `filter` skips are marked as such.  A virtual root is included at the end, as
+/// `workspace root`.
+fn load_workspace(manifest_path: &Path, filter: &MemberFilter) -> CargoResult<Vec<Member>> {
+    let ws = cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .exec()
+        .with_context(|| "Invalid workspace")?;
+    let ws = WorkspaceMetadata::new(ws);
+    let root_path = ws.root_manifest_path();
+    let selection = ws.select_members(filter)?;
+    let mut members = ws
+        .members()
+        .iter()
+        .map(|p| {
+            Ok(Member {
+                name: p.name.clone(),
+                manifest: LocalManifest::try_new(p.manifest_path.as_std_path())?,
+                is_root: p.manifest_path.as_std_path() == root_path,
+                skipped: !selection
+                    .selected
+                    .iter()
+                    .any(|selected| selected.id == p.id),
+                modified: false,
+            })
+        })
+        .collect::<CargoResult<Vec<_>>>()?;
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    if !members.iter().any(|member| member.is_root) {
+        members.push(Member {
+            name: "workspace root".to_owned(),
+            manifest: LocalManifest::try_new(&root_path)?,
+            is_root: true,
+            skipped: false,
+            modified: false,
+        });
+    }
+    Ok(members)
+}
+
+/// Look up `dep` in the table at `section`
+fn get_dependency<'m>(
+    manifest: &'m LocalManifest,
+    section: &[String],
+    dep: &str,
+) -> Option<&'m toml_edit::Item> {
+    let mut item = manifest.data.as_item();
+    for key in section {
+        item = item.get(key)?;
+    }
+    item.get(dep)
+}
+
+/// Whether `dep` in the table at `section` is `workspace = true`
+fn is_inherited(manifest: &LocalManifest, section: &[String], dep: &str) -> bool {
+    get_dependency(manifest, section, dep)
+        .and_then(|dep| dep.get("workspace"))
+        .and_then(|workspace| workspace.as_bool())
+        .unwrap_or(false)
+}
+
+/// Guess the workspace root of `manifest` the way cargo does, unless it is a root itself
+fn find_ws_root_path(manifest: &LocalManifest) -> Option<PathBuf> {
+    if manifest.data.contains_key("workspace") {
+        return None;
+    }
+    let dir = manifest.path.parent()?;
+    if let Some(root) = manifest
+        .data
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(|root| root.as_str())
+    {
+        return Some(dir.join(root).join("Cargo.toml"));
+    }
+    dir.ancestors()
+        .skip(1)
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|path| {
+            LocalManifest::try_new(path)
+                .map(|root| includes_package(&root, dir))
+                .unwrap_or(false)
+        })
+}
+
+/// Whether `root` is a workspace root that doesn't `exclude` the package in `package_dir`
+///
+/// Cargo keeps looking further up for a root when the nearest one excludes the package.
+fn includes_package(root: &LocalManifest, package_dir: &Path) -> bool {
+    let workspace = match root.data.get("workspace").and_then(|ws| ws.as_table_like()) {
+        Some(workspace) => workspace,
+        None => return false,
+    };
+    let root_dir = root.path.parent().expect("manifests are in a directory");
+    let paths = |key: &str| {
+        workspace
+            .get(key)
+            .and_then(|paths| paths.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.as_str())
+            .map(|path| root_dir.join(path))
+            .collect::<Vec<_>>()
+    };
+    let excluded = paths("exclude")
+        .iter()
+        .any(|exclude| package_dir.starts_with(exclude));
+    let explicit_member = paths("members").iter().any(|member| member == package_dir);
+    explicit_member || !excluded
+}
+
+/// Entries overriding `package`, as the path of their table along with their key
+fn find_patches(manifest: &LocalManifest, package: &str) -> Vec<(Vec<String>, String)> {
+    let mut patches = Vec::new();
+    if let Some(sources) = manifest.data.get("patch").and_then(|p| p.as_table_like()) {
+        for (source, table) in sources.iter() {
+            let table = match table.as_table_like() {
+                Some(table) => table,
+                None => continue,
+            };
+            for (key, entry) in table.iter() {
+                let name = entry
+                    .get("package")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or(key);
+                if name == package {
+                    patches.push((vec!["patch".to_owned(), source.to_owned()], key.to_owned()));
+                }
+            }
+        }
+    }
+    if let Some(replace) = manifest.data.get("replace").and_then(|r| r.as_table_like()) {
+        for (spec, _) in replace.iter() {
+            // Package ID specs look like `foo:1.0.0` or `foo@1.0.0`
+            let name = spec.split([':', '@']).next().unwrap_or(spec);
+            if name == package {
+                patches.push((vec!["replace".to_owned()], spec.to_owned()));
+            }
+        }
+    }
+    patches
+}
@@ -0,0 +1,156 @@
+//! Describing removed dependencies, so they can be added back.
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::{find, Dependency, Source};
+
+/// Command line for `cargo add` recreating `dep` in the table at `section`
+///
+/// Paths are given relative to `cwd`.  `manifest_path` is only needed when the manifest isn't
+/// the one `cargo add` would find on its own.
+pub(crate) fn add_command(
+    dep: &Dependency,
+    section: &[String],
+    manifest_path: Option<&Path>,
+    cwd: &Path,
+) -> String {
+    let with_version = |version: Option<&str>| match version {
+        Some(version) => format!("{}@{version}", dep.name),
+        None => dep.name.clone(),
+    };
+    let mut args = vec!["cargo".to_owned(), "add".to_owned()];
+    match dep.source() {
+        Some(Source::Registry(src)) => args.push(with_version(Some(src.version.as_str()))),
+        Some(Source::Path(src)) => {
+            args.push(with_version(src.version.as_deref()));
+            args.push("--path".to_owned());
+            args.push(relative_path(&src.path, cwd));
+        }
+        Some(Source::Git(src)) => {
+            args.push(with_version(src.version.as_deref()));
+            args.push("--git".to_owned());
+            args.push(src.git.clone());
+            for (flag, value) in [
+                ("--branch", &src.branch),
+                ("--tag", &src.tag),
+                ("--rev", &src.rev),
+            ] {
+                if let Some(value) = value {
+                    args.push(flag.to_owned());
+                    args.push(value.clone());
+                }
+            }
+        }
+        Some(Source::Workspace(_)) | None => args.push(dep.name.clone()),
+    }
+    if let Some(rename) = dep.rename() {
+        args.push("--rename".to_owned());
+        args.push(rename.to_owned());
+    }
+    if let Some(registry) = dep.registry() {
+        args.push("--registry".to_owned());
+        args.push(registry.to_owned());
+    }
+    match section.last().map(String::as_str) {
+        Some("dev-dependencies") => args.push("--dev".to_owned()),
+        Some("build-dependencies") => args.push("--build".to_owned()),
+        _ => {}
+    }
+    if section.len() >= 3 {
+        args.push("--target".to_owned());
+        args.push(section[1].clone());
+    }
+    if dep.default_features() == Some(false) {
+        args.push("--no-default-features".to_owned());
+    }
+    if let Some(features) = dep.features.as_ref().filter(|f| !f.is_empty()) {
+        args.push("--features".to_owned());
+        args.push(features.join(","));
+    }
+    if dep.optional() == Some(true) {
+        args.push("--optional".to_owned());
+    }
+    if let Some(manifest_path) = manifest_path {
+        args.push("--manifest-path".to_owned());
+        args.push(relative_path(manifest_path, cwd));
+    }
+
+    args.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `manifest_path` is the manifest cargo finds from the current directory
+pub(crate) fn is_default_manifest(manifest_path: &Path) -> bool {
+    find(None)
+        .ok()
+        .and_then(|default| dunce::canonicalize(default).ok())
+        .map(|default| default == manifest_path)
+        .unwrap_or(false)
+}
+
+pub(crate) fn relative_path(path: &Path, cwd: &Path) -> String {
+    let path = pathdiff::diff_paths(path, cwd).unwrap_or_else(|| path.to_owned());
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Quote `arg` for POSIX shells, if needed
+pub(crate) fn quote(arg: &str) -> Cow<'_, str> {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./@:,+=".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        Cow::Borrowed(arg)
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{PathSource, RegistrySource};
+
+    fn section(path: &[&str]) -> Vec<String> {
+        path.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn registry_dependency() {
+        let dep = Dependency::new("serde")
+            .set_source(RegistrySource::new("1.0"))
+            .set_features(vec!["derive".to_owned(), "rc".to_owned()])
+            .set_default_features(false)
+            .set_optional(true);
+        let cwd = Path::new("/project");
+        assert_eq!(
+            add_command(&dep, &section(&["dev-dependencies"]), None, cwd),
+            "cargo add serde@1.0 --dev --no-default-features --features derive,rc --optional"
+        );
+    }
+
+    #[test]
+    fn target_and_path() {
+        let dep = Dependency::new("winapi")
+            .set_source(PathSource::new("/project/vendor/winapi").set_version("0.3"))
+            .set_rename("win");
+        let cwd = Path::new("/project");
+        assert_eq!(
+            add_command(
+                &dep,
+                &section(&["target", "cfg(windows)", "dependencies"]),
+                Some(Path::new("/project/member/Cargo.toml")),
+                cwd
+            ),
+            "cargo add winapi@0.3 --path vendor/winapi --rename win --target 'cfg(windows)' --manifest-path member/Cargo.toml"
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("serde@1.0"), "serde@1.0");
+        assert_eq!(quote(">=1.0, <2"), "'>=1.0, <2'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+}
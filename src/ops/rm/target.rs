@@ -0,0 +1,112 @@
+//! Which `[target]` tables apply to the platform passed to `--target`
+use std::process::Command;
+use std::str::FromStr;
+
+use cargo_platform::{Cfg, Platform};
+
+use crate::errors::*;
+
+/// `cfg` values of the target triple passed to `--target`
+#[derive(Debug, Default)]
+pub(crate) struct TargetCfg(Option<Vec<Cfg>>);
+
+impl TargetCfg {
+    /// Ask rustc for the `cfg` values of `target`, if it is a target triple
+    pub(crate) fn new(target: &str) -> CargoResult<Self> {
+        match Platform::from_str(target.trim()) {
+            Ok(Platform::Name(triple)) => rustc_cfg(&triple).map(|cfg| Self(Some(cfg))),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+/// Whether the `[target]` table `key` applies to `target`, the platform passed to `--target`
+///
+/// Like cargo, whitespace within a `cfg(...)` expression is not significant, and a target triple
+/// also matches the `cfg(...)` expressions that hold for it.  A `cfg(...)` expression only
+/// matches tables with the same expression.
+pub(crate) fn target_matches(key: &str, target: &str, cfg: &TargetCfg) -> bool {
+    let normalize = |target: &str| {
+        target
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    if normalize(key) == normalize(target) {
+        return true;
+    }
+
+    match (
+        Platform::from_str(key.trim()),
+        Platform::from_str(target.trim()),
+    ) {
+        (Ok(Platform::Cfg(expr)), Ok(Platform::Name(_))) => {
+            cfg.0.as_deref().map_or(false, |cfg| expr.matches(cfg))
+        }
+        (Ok(key), Ok(target)) => key == target,
+        _ => false,
+    }
+}
+
+/// Ask rustc for the `cfg` values of `triple`, like cargo does
+fn rustc_cfg(triple: &str) -> CargoResult<Vec<Cfg>> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .args(["--print", "cfg", "--target", triple])
+        .output()
+        .context("failed to run `rustc`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`rustc --print cfg --target {triple}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| Cfg::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn windows_gnu() -> TargetCfg {
+        let cfg = ["windows", r#"target_os="windows""#, r#"target_env="gnu""#]
+            .iter()
+            .map(|cfg| Cfg::from_str(cfg).unwrap())
+            .collect();
+        TargetCfg(Some(cfg))
+    }
+
+    #[test]
+    fn triple() {
+        let cfg = windows_gnu();
+        let triple = "x86_64-pc-windows-gnu";
+        assert!(target_matches("x86_64-pc-windows-gnu", triple, &cfg));
+        assert!(target_matches("cfg(windows)", triple, &cfg));
+        assert!(target_matches("cfg(any(unix, windows))", triple, &cfg));
+        assert!(target_matches(r#"cfg(target_env = "gnu")"#, triple, &cfg));
+        assert!(!target_matches(r#"cfg(target_env = "msvc")"#, triple, &cfg));
+        assert!(!target_matches("cfg(unix)", triple, &cfg));
+        assert!(!target_matches("x86_64-pc-windows-msvc", triple, &cfg));
+    }
+
+    #[test]
+    fn cfg_expression() {
+        let cfg = TargetCfg::default();
+        assert!(target_matches("cfg(windows)", "cfg( windows )", &cfg));
+        assert!(target_matches(
+            "cfg(any(unix,windows))",
+            "cfg(any(unix, windows))",
+            &cfg
+        ));
+        assert!(!target_matches("cfg(unix)", "cfg(windows)", &cfg));
+        assert!(!target_matches(
+            "x86_64-pc-windows-gnu",
+            "cfg(windows)",
+            &cfg
+        ));
+    }
+}
@@ -0,0 +1,209 @@
+//! Finding dependencies that are never used, from compiler diagnostics.
+use std::collections::{BTreeMap, BTreeSet};
+use std::process::Command;
+
+use cargo_metadata::{Message, Target};
+
+use crate::errors::*;
+use crate::LocalManifest;
+
+const LINT: &str = "unused_crate_dependencies";
+
+/// Dependencies of the package at `manifest` that `cargo check` reports as unused
+///
+/// A dependency only counts as unused if every target that can use it reports it, e.g. a
+/// dev-dependency has to be unused by the tests, benches, and examples alike.  Returns the keys of
+/// the dependencies, in the order of their tables.  `locked` and `offline` are passed on to
+/// `cargo check`.
+pub(crate) fn find_unused(
+    manifest: &LocalManifest,
+    locked: bool,
+    offline: bool,
+) -> CargoResult<Vec<String>> {
+    let ws = cargo_metadata::MetadataCommand::new()
+        .manifest_path(&manifest.path)
+        .no_deps()
+        .exec()
+        .with_context(|| "Invalid workspace")?;
+    let package = ws
+        .packages
+        .iter()
+        .find(|package| package.manifest_path.as_std_path() == manifest.path)
+        .with_context(|| "`--unused` requires running against a package, not a virtual manifest")?;
+
+    let unused_by_target = check(manifest, &package.id, locked, offline)?;
+
+    let mut unused = Vec::new();
+    for (key, kinds) in dependency_kinds(manifest) {
+        let ident = key.replace('-', "_");
+        let mut relevant = unused_by_target
+            .iter()
+            .filter(|(target, _)| kinds.iter().any(|kind| kind.applies_to(target)))
+            .peekable();
+        if relevant.peek().is_none() {
+            continue;
+        }
+        if relevant.all(|(_, crates)| crates.contains(&ident)) {
+            unused.push(key);
+        }
+    }
+    Ok(unused)
+}
+
+/// Run `cargo check` on all targets of the package, collecting the crates each target reports
+/// as unused
+fn check(
+    manifest: &LocalManifest,
+    package_id: &cargo_metadata::PackageId,
+    locked: bool,
+    offline: bool,
+) -> CargoResult<BTreeMap<TargetId, BTreeSet<String>>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut cmd = Command::new(cargo);
+    cmd.arg("check")
+        .arg("--all-targets")
+        .arg("--message-format=json")
+        .arg("--manifest-path")
+        .arg(&manifest.path);
+    if locked {
+        cmd.arg("--locked");
+    }
+    if offline {
+        cmd.arg("--offline");
+    }
+    // `CARGO_ENCODED_RUSTFLAGS` takes precedence over `RUSTFLAGS` if set
+    let lint_flag = format!("-W{}", LINT.replace('_', "-"));
+    if let Some(mut flags) = std::env::var_os("CARGO_ENCODED_RUSTFLAGS") {
+        if !flags.is_empty() {
+            flags.push("\x1f");
+        }
+        flags.push(&lint_flag);
+        cmd.env("CARGO_ENCODED_RUSTFLAGS", flags);
+    } else {
+        let mut flags = std::env::var_os("RUSTFLAGS").unwrap_or_default();
+        if !flags.is_empty() {
+            flags.push(" ");
+        }
+        flags.push(&lint_flag);
+        cmd.env("RUSTFLAGS", flags);
+    }
+
+    let output = cmd
+        .output()
+        .with_context(|| "Failed to run `cargo check`")?;
+    let mut unused_by_target = BTreeMap::<TargetId, BTreeSet<String>>::new();
+    let mut success = false;
+    for message in Message::parse_stream(output.stdout.as_slice()) {
+        match message.with_context(|| "Invalid output from `cargo check`")? {
+            Message::CompilerArtifact(artifact) if &artifact.package_id == package_id => {
+                unused_by_target
+                    .entry(TargetId::new(&artifact.target))
+                    .or_default();
+            }
+            Message::CompilerMessage(msg) if &msg.package_id == package_id => {
+                let is_lint = msg
+                    .message
+                    .code
+                    .as_ref()
+                    .map(|code| code.code == LINT)
+                    .unwrap_or(false);
+                if !is_lint {
+                    continue;
+                }
+                // "external crate `foo` unused in `bar`: remove the dependency or add ..."
+                if let Some(name) = msg.message.message.split('`').nth(1) {
+                    unused_by_target
+                        .entry(TargetId::new(&msg.target))
+                        .or_default()
+                        .insert(name.to_owned());
+                }
+            }
+            Message::BuildFinished(finished) => success = finished.success,
+            _ => {}
+        }
+    }
+    if !success || !output.status.success() {
+        anyhow::bail!(
+            "`cargo check` failed, so unused dependencies can't be found:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(unused_by_target)
+}
+
+/// Dependency keys along with the kinds of tables they are in, in the order of the tables
+fn dependency_kinds(manifest: &LocalManifest) -> Vec<(String, Vec<DepKind>)> {
+    let mut deps: Vec<(String, Vec<DepKind>)> = Vec::new();
+    for (table, key) in manifest.get_dependency_keys() {
+        let kind = match table.last().map(String::as_str) {
+            Some("dev-dependencies") => DepKind::Development,
+            Some("build-dependencies") => DepKind::Build,
+            _ => DepKind::Normal,
+        };
+        match deps.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, kinds)) => kinds.push(kind),
+            None => deps.push((key, vec![kind])),
+        }
+    }
+    deps
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DepKind {
+    Normal,
+    Development,
+    Build,
+}
+
+impl DepKind {
+    /// Whether a dependency of this kind is available to `target`
+    fn applies_to(self, target: &TargetId) -> bool {
+        let is_build_script = target.kinds.iter().any(|kind| kind == "custom-build");
+        match self {
+            // Dev-dependencies are only available to libs and bins when built as tests, which
+            // reports them as unused just the same
+            Self::Normal | Self::Development => !is_build_script,
+            Self::Build => is_build_script,
+        }
+    }
+}
+
+/// Target of the package, e.g. the `lib` or a `test`
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TargetId {
+    kinds: Vec<String>,
+    name: String,
+}
+
+impl TargetId {
+    fn new(target: &Target) -> Self {
+        Self {
+            kinds: target.kind.clone(),
+            name: target.name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(kind: &str, name: &str) -> TargetId {
+        TargetId {
+            kinds: vec![kind.to_owned()],
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn kinds_apply_to_targets() {
+        let lib = target("lib", "foo");
+        let test = target("test", "it");
+        let build = target("custom-build", "build-script-build");
+        assert!(DepKind::Normal.applies_to(&lib));
+        assert!(DepKind::Development.applies_to(&test));
+        assert!(!DepKind::Normal.applies_to(&build));
+        assert!(DepKind::Build.applies_to(&build));
+        assert!(!DepKind::Build.applies_to(&lib));
+    }
+}
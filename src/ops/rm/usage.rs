@@ -0,0 +1,157 @@
+//! Heuristic search for uses of a crate in a package's source.
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::LocalManifest;
+
+/// Directories cargo looks for targets in by default
+const TARGET_DIRS: &[&str] = &["src", "tests", "examples", "benches"];
+/// Tables holding explicitly declared targets
+const TARGET_TABLES: &[&str] = &["lib", "bin", "test", "example", "bench"];
+
+/// Place in the source that appears to refer to a crate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Use {
+    /// File, relative to the package root
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+}
+
+/// Look for `use <crate>`, `<crate>::` and `extern crate <crate>` in the package's targets
+///
+/// `dep` is the dependency's key, so `foo-bar` is searched for as `foo_bar`.  This is not a
+/// compiler: matches in comments and strings are reported too.
+pub(crate) fn find_uses(manifest: &LocalManifest, dep: &str) -> CargoResult<Vec<Use>> {
+    let root = match manifest.path.parent() {
+        Some(root) => root,
+        None => return Ok(Vec::new()),
+    };
+    let ident = dep.replace('-', "_");
+
+    let mut uses = Vec::new();
+    for path in source_files(manifest, root) {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            // Not UTF-8, or removed in the meantime
+            Err(_) => continue,
+        };
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_owned();
+        for (idx, line) in content.lines().enumerate() {
+            if mentions_crate(line, &ident) {
+                uses.push(Use {
+                    path: relative.clone(),
+                    line: idx + 1,
+                });
+            }
+        }
+    }
+    Ok(uses)
+}
+
+/// Rust files of the package's targets, both in the default locations and as declared
+fn source_files(manifest: &LocalManifest, root: &Path) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    for dir in TARGET_DIRS {
+        collect_rs_files(&root.join(dir), &mut files);
+    }
+    let build = manifest
+        .data
+        .get("package")
+        .and_then(|package| package.get("build"))
+        .and_then(|build| build.as_str())
+        .unwrap_or("build.rs");
+    let mut declared = vec![build.to_owned()];
+    for table in TARGET_TABLES {
+        let targets = match manifest.data.get(table) {
+            Some(toml_edit::Item::ArrayOfTables(targets)) => targets.iter().collect::<Vec<_>>(),
+            Some(toml_edit::Item::Table(target)) => vec![target],
+            _ => continue,
+        };
+        declared.extend(
+            targets
+                .into_iter()
+                .filter_map(|target| target.get("path")?.as_str())
+                .map(|path| path.to_owned()),
+        );
+    }
+    for path in declared {
+        let path = root.join(path);
+        if path.is_file() {
+            files.insert(path);
+        }
+    }
+    files
+}
+
+fn collect_rs_files(dir: &Path, files: &mut BTreeSet<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_rs_files(&path, files);
+        } else if path.extension().map(|ext| ext == "rs").unwrap_or(false) {
+            files.insert(path);
+        }
+    }
+}
+
+/// Whether `line` looks like it refers to the crate `ident`
+fn mentions_crate(line: &str, ident: &str) -> bool {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(ident).any(|(start, _)| {
+        let before = &line[..start];
+        let after = &line[start + ident.len()..];
+        if before
+            .chars()
+            .next_back()
+            .map(is_ident_char)
+            .unwrap_or(false)
+            || after.chars().next().map(is_ident_char).unwrap_or(false)
+        {
+            return false;
+        }
+        let before = before.trim_end();
+        after.trim_start().starts_with("::")
+            || before.ends_with("extern crate")
+            || before == "use"
+            || before.ends_with(" use")
+            || before.ends_with("{use")
+            || before.ends_with(";use")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mentions() {
+        assert!(mentions_crate("use once_cell::sync::Lazy;", "once_cell"));
+        assert!(mentions_crate("    pub use once_cell;", "once_cell"));
+        assert!(mentions_crate("extern crate once_cell;", "once_cell"));
+        assert!(mentions_crate(
+            "static X: once_cell::sync::Lazy<u8> = todo!();",
+            "once_cell"
+        ));
+        assert!(mentions_crate(
+            "let x = ::once_cell::sync::Lazy::new(f);",
+            "once_cell"
+        ));
+    }
+
+    #[test]
+    fn unrelated() {
+        assert!(!mentions_crate("use my_once_cell::Lazy;", "once_cell"));
+        assert!(!mentions_crate("use once_cell_ext::Lazy;", "once_cell"));
+        assert!(!mentions_crate("let once_cell = 5;", "once_cell"));
+        assert!(!mentions_crate(
+            "// uses a once_cell internally",
+            "once_cell"
+        ));
+    }
+}
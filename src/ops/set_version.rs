@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+
+use super::{ChangeKind, ManifestChange};
+use crate::errors::*;
+use crate::{
+    FsResolver, LocalManifest, MemberFilter, SkipReason, TargetVersion, WorkspaceMetadata,
+};
+
+/// What [`set_version`] should do
+#[derive(Clone, Debug, Default)]
+pub struct SetVersionOptions {
+    /// Version to change packages to
+    pub target: TargetVersion,
+    /// Version metadata field to set (e.g. a wrapped libraries version)
+    pub metadata: Option<String>,
+    /// Manifest of the workspace, instead of the one found from the current directory
+    pub manifest_path: Option<PathBuf>,
+    /// Packages to change, as selectors for [`crate::select_packages`]
+    ///
+    /// All workspace members are changed when empty.
+    pub packages: Vec<String>,
+    /// Change all packages in the workspace
+    pub workspace: bool,
+    /// Packages not to change, as selectors for [`crate::select_packages`]
+    pub exclude: Vec<String>,
+    /// Report the changes without writing them
+    pub dry_run: bool,
+    /// Run without accessing the network
+    pub offline: bool,
+    /// Require `Cargo.lock` to be up to date
+    pub locked: bool,
+}
+
+/// Edits made by [`set_version`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetVersionReport {
+    /// Every edit, in the order it was made
+    pub changes: Vec<ManifestChange>,
+}
+
+/// Change the version of packages, and the requirements of their dependents in the workspace
+pub fn set_version(options: &SetVersionOptions) -> CargoResult<SetVersionReport> {
+    set_version_with(options, |_| Ok(()))
+}
+
+/// Like [`set_version`], with `on_change` called for each edit as it is made
+///
+/// An error from `on_change` aborts the operation.
+pub fn set_version_with(
+    options: &SetVersionOptions,
+    on_change: impl FnMut(&ManifestChange) -> CargoResult<()>,
+) -> CargoResult<SetVersionReport> {
+    let ws = WorkspaceMetadata::load(
+        options.manifest_path.as_deref(),
+        options.locked,
+        options.offline,
+    )?;
+    set_version_in(&ws, options, on_change)
+}
+
+/// Like [`set_version_with`], for the already loaded `ws`
+///
+/// `cargo metadata` is only run again to update `Cargo.lock` once manifests were written.
+pub fn set_version_in(
+    ws: &WorkspaceMetadata,
+    options: &SetVersionOptions,
+    mut on_change: impl FnMut(&ManifestChange) -> CargoResult<()>,
+) -> CargoResult<SetVersionReport> {
+    let SetVersionOptions {
+        target,
+        metadata,
+        manifest_path,
+        packages,
+        workspace,
+        exclude,
+        dry_run,
+        offline,
+        locked,
+    } = options;
+    let mut report = SetVersionReport::default();
+    let mut record = |change: ManifestChange| -> CargoResult<()> {
+        on_change(&change)?;
+        report.changes.push(change);
+        Ok(())
+    };
+
+    let root_manifest_path = ws.root_manifest_path();
+    let workspace_members = ws.members();
+
+    let filter = MemberFilter {
+        include: if *workspace {
+            Vec::new()
+        } else {
+            packages.clone()
+        },
+        exclude: exclude.clone(),
+        ..Default::default()
+    };
+    let selection = ws.select_members(&filter)?;
+    let mut selected = selection.selected.clone();
+
+    let update_workspace_version;
+    let mut changed = false;
+    if selected.len() == workspace_members.len() {
+        // Fast path
+        update_workspace_version = true;
+    } else {
+        let explicit_selected = selected
+            .iter()
+            .filter(|p| {
+                LocalManifest::try_new(Path::new(&p.manifest_path))
+                    .map_or(false, |m| m.version_is_inherited())
+            })
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        update_workspace_version = !explicit_selected.is_empty();
+        if update_workspace_version {
+            let implicit = workspace_members
+                .iter()
+                .filter(|i| !selected.iter().any(|s| i.id == s.id))
+                .filter(|i| {
+                    LocalManifest::try_new(Path::new(&i.manifest_path))
+                        .map_or(false, |m| m.version_is_inherited())
+                })
+                .collect::<Vec<_>>();
+            let exclude_implicit = implicit
+                .iter()
+                .filter(|p| selection.is_skipped_for(p, SkipReason::Excluded))
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>();
+            if !exclude_implicit.is_empty() {
+                anyhow::bail!(
+                    "Cannot exclude {} package(s) when {} package(s) modify `workspace.package.version`",
+                    exclude_implicit.join(", "),
+                    explicit_selected.join(", ")
+                );
+            }
+            selected.extend(implicit);
+        }
+    }
+
+    if update_workspace_version {
+        let mut ws_manifest = LocalManifest::try_new(&root_manifest_path)?;
+        if let Some(current) = ws_manifest.get_workspace_version() {
+            if let Some(next) = target.bump(&current, metadata.as_deref())? {
+                record(ManifestChange {
+                    package: "workspace".to_owned(),
+                    manifest_path: root_manifest_path.clone(),
+                    kind: ChangeKind::WorkspaceVersion,
+                    old: current.to_string(),
+                    new: next.to_string(),
+                })?;
+                ws_manifest.set_workspace_version(&next);
+                changed = true;
+                if !dry_run {
+                    ws_manifest.write()?;
+                }
+
+                // Deferring `update_dependents` to the per-package logic
+            }
+        }
+    }
+
+    for package in selected {
+        let current = &package.version;
+        let next = target.bump(current, metadata.as_deref())?;
+        if let Some(next) = next {
+            let mut manifest = LocalManifest::try_new(Path::new(&package.manifest_path))?;
+            let kind = if manifest.version_is_inherited() {
+                ChangeKind::InheritedPackageVersion
+            } else {
+                ChangeKind::PackageVersion
+            };
+            record(ManifestChange {
+                package: package.name.clone(),
+                manifest_path: manifest.path.clone(),
+                kind: kind.clone(),
+                old: current.to_string(),
+                new: next.to_string(),
+            })?;
+            if kind == ChangeKind::PackageVersion {
+                manifest.set_package_version(&next);
+                changed = true;
+                if !dry_run {
+                    manifest.write()?;
+                }
+            }
+
+            let crate_root =
+                dunce::canonicalize(package.manifest_path.parent().expect("at least a parent"))?;
+            update_dependents(
+                &crate_root,
+                &next,
+                &root_manifest_path,
+                workspace_members,
+                *dry_run,
+                &mut record,
+            )?
+        }
+    }
+
+    if changed && !dry_run {
+        // Refresh `Cargo.lock` for the new versions
+        WorkspaceMetadata::load(manifest_path.as_deref(), *locked, *offline)?;
+    }
+
+    Ok(report)
+}
+
+fn update_dependents(
+    crate_root: &Path,
+    next: &semver::Version,
+    root_manifest_path: &Path,
+    workspace_members: &[cargo_metadata::Package],
+    dry_run: bool,
+    record: &mut dyn FnMut(ManifestChange) -> CargoResult<()>,
+) -> CargoResult<()> {
+    // This is redundant with iterating over `workspace_members`
+    // - As `dependency_tables_mut` returns workspace dependencies
+    // - If there is a root package
+    //
+    // But split this out for
+    // - Virtual manifests
+    // - Nicer message to the user
+    {
+        update_dependent(
+            crate_root,
+            next,
+            root_manifest_path,
+            "workspace",
+            dry_run,
+            record,
+        )?;
+    }
+
+    // Members that were skipped still need to build against the new version
+    for member in workspace_members.iter() {
+        update_dependent(
+            crate_root,
+            next,
+            member.manifest_path.as_std_path(),
+            &member.name,
+            dry_run,
+            record,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn update_dependent(
+    crate_root: &Path,
+    next: &semver::Version,
+    manifest_path: &Path,
+    name: &str,
+    dry_run: bool,
+    record: &mut dyn FnMut(ManifestChange) -> CargoResult<()>,
+) -> CargoResult<()> {
+    let mut dep_manifest = LocalManifest::try_new(manifest_path)?;
+    let dep_crate_root = dep_manifest
+        .path
+        .parent()
+        .expect("at least a parent")
+        .to_owned();
+
+    let changes =
+        dep_manifest.upgrade_path_requirements(&dep_crate_root, crate_root, next, &FsResolver)?;
+    for change in &changes {
+        record(ManifestChange {
+            package: name.to_owned(),
+            manifest_path: dep_manifest.path.clone(),
+            kind: ChangeKind::DependencyRequirement {
+                table: change.table.clone(),
+                dependency: change.dependency.clone(),
+            },
+            old: change.old.clone(),
+            new: change.new.clone(),
+        })?;
+    }
+    if !changes.is_empty() && !dry_run {
+        dep_manifest.write()?;
+    }
+
+    Ok(())
+}
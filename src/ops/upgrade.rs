@@ -0,0 +1,833 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::process::Command;
+
+use indexmap::IndexMap;
+use semver::{Op, VersionReq};
+use url::Url;
+
+use super::{ChangeKind, ManifestChange};
+use crate::errors::*;
+use crate::{
+    find, get_compatible_dependency_from, get_latest_dependency_from, matches_glob,
+    registry_source_with, registry_url, update_registry_index_with, upgrade_requirement, CrateSpec,
+    Dependency, EditConfig, IndexUpdate, LocalManifest, MemberFilter, Source, VersionSource,
+    WorkspaceMetadata,
+};
+
+/// What [`upgrade`] should do
+#[derive(Clone, Debug)]
+pub struct UpgradeOptions {
+    /// Manifest of the workspace, instead of the one found from the current directory
+    pub manifest_path: Option<PathBuf>,
+    /// Dependencies to upgrade, as `<name>[@<version>]`
+    ///
+    /// All dependencies are upgraded when empty.  A dependency given with a version has its
+    /// requirement set to that version.
+    pub packages: Vec<String>,
+    /// Dependencies not to upgrade, by name or glob pattern
+    ///
+    /// The `upgrade.exclude` setting is used when empty.
+    pub exclude: Vec<String>,
+    /// Upgrade to the latest compatible version
+    pub compatible: bool,
+    /// Upgrade to the latest incompatible version
+    pub incompatible: bool,
+    /// Upgrade pinned requirements to the latest incompatible version
+    ///
+    /// The `upgrade.pinned` setting is used when `None`.
+    pub pinned: Option<bool>,
+    /// Update the upgraded dependencies in `Cargo.lock`, along with their own dependencies
+    pub recursive: bool,
+    /// Look versions up in the registry even if they were recently cached
+    pub refresh: bool,
+    /// Ignore cargo-edit's workspace and user configuration
+    pub no_config: bool,
+    /// Report the upgrades without writing them
+    pub dry_run: bool,
+    /// Run without accessing the network
+    pub offline: bool,
+    /// Require `Cargo.lock` to be up to date
+    pub locked: bool,
+    /// Both `locked` and `offline`, like cargo's `--frozen`
+    pub frozen: bool,
+}
+
+impl Default for UpgradeOptions {
+    fn default() -> Self {
+        Self {
+            manifest_path: None,
+            packages: Vec::new(),
+            exclude: Vec::new(),
+            compatible: true,
+            incompatible: false,
+            pinned: None,
+            recursive: true,
+            refresh: false,
+            no_config: false,
+            dry_run: false,
+            offline: false,
+            locked: false,
+            frozen: false,
+        }
+    }
+}
+
+impl UpgradeOptions {
+    fn locked(&self) -> bool {
+        self.locked || self.frozen
+    }
+
+    fn offline(&self) -> bool {
+        self.offline || self.frozen
+    }
+}
+
+/// Work done by [`upgrade`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpgradeReport {
+    /// Every edit, in the order it was made
+    pub changes: Vec<ManifestChange>,
+    /// Every dependency that was checked, upgraded or not, in the order they were checked
+    pub dependencies: Vec<DependencyUpgrade>,
+}
+
+/// Dependency checked by [`upgrade`]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DependencyUpgrade {
+    /// Workspace member owning the manifest, or `workspace` for a virtual manifest
+    pub package: String,
+    /// Manifest the dependency is in
+    pub manifest_path: PathBuf,
+    /// Name of the dependency
+    pub name: String,
+    /// Name the dependency is renamed to, if it is
+    pub rename: Option<String>,
+    /// Requirement before the upgrade, `None` for git and path dependencies
+    pub old_version_req: Option<String>,
+    /// Latest version the old requirement is compatible with
+    pub compatible_version: Option<String>,
+    /// Latest version in the registry
+    pub latest_version: Option<String>,
+    /// Requirement after the upgrade, `None` for git and path dependencies
+    pub new_version_req: Option<String>,
+    /// Why the requirement isn't at the latest version, if it isn't
+    pub reason: Option<UpgradeReason>,
+}
+
+/// Why [`upgrade`] left a requirement as it was
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UpgradeReason {
+    /// The requirement already allows the latest version
+    Unchanged,
+    /// There is a compatible upgrade, but `compatible` is off
+    Compatible,
+    /// There is an incompatible upgrade, but `incompatible` is off
+    Incompatible,
+    /// The requirement is pinned, and `pinned` is off
+    Pinned,
+    /// The dependency comes from git
+    GitSource,
+    /// The dependency comes from a path
+    PathSource,
+    /// The dependency wasn't selected, or was excluded
+    Excluded,
+}
+
+/// Progress of [`upgrade_with`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpgradeEvent {
+    /// A registry index is being updated
+    Index(IndexUpdate),
+    /// The dependencies of a manifest are about to be checked
+    Checking {
+        /// Workspace member owning the manifest, `None` for a virtual manifest
+        package: Option<String>,
+        /// Manifest being checked
+        manifest_path: PathBuf,
+    },
+    /// A dependency entry couldn't be read, so it was skipped
+    UnsupportedEntry {
+        /// Key of the dependency
+        key: String,
+        /// What is wrong with the entry
+        error: String,
+    },
+    /// A dependency without a version requirement, from neither git nor a path, was skipped
+    UnknownSource {
+        /// Key of the dependency
+        key: String,
+        /// Where the dependency comes from, `unknown` if it has no source
+        source: String,
+    },
+    /// The dependencies of a manifest were checked
+    Checked {
+        /// Workspace member owning the manifest, `None` for a virtual manifest
+        package: Option<String>,
+        /// Every dependency that was checked
+        dependencies: Vec<DependencyUpgrade>,
+    },
+    /// Git dependencies are being updated in `Cargo.lock`
+    UpgradingGitDependencies,
+    /// The upgraded dependencies are being updated in `Cargo.lock`, with their own dependencies
+    UpgradingRecursiveDependencies,
+    /// `cargo update` updated `Cargo.lock`
+    LockfileUpdated {
+        /// What `cargo update` printed
+        output: String,
+    },
+}
+
+/// Upgrade dependency requirements in the workspace's manifests, and update `Cargo.lock`
+pub fn upgrade(options: &UpgradeOptions) -> CargoResult<UpgradeReport> {
+    upgrade_with(options, |_| Ok(()))
+}
+
+/// Like [`upgrade`], with `on_event` called as the upgrade progresses
+///
+/// An error from `on_event` aborts the operation.
+pub fn upgrade_with(
+    options: &UpgradeOptions,
+    mut on_event: impl FnMut(&UpgradeEvent) -> CargoResult<()>,
+) -> CargoResult<UpgradeReport> {
+    let mut report = UpgradeReport::default();
+    if !options.offline() {
+        let url = registry_url(&find(options.manifest_path.as_deref())?, None)?;
+        update_registry_index_with(&url, |update| {
+            on_event(&UpgradeEvent::Index(update.clone()))
+        })?;
+    }
+
+    let ws = WorkspaceMetadata::load(
+        options.manifest_path.as_deref(),
+        options.locked(),
+        options.offline(),
+    )?;
+    let root_manifest_path = ws.root_manifest_path();
+    let config = if options.no_config {
+        EditConfig::default()
+    } else {
+        EditConfig::load_from_workspace(&ws)?
+    };
+    let exclude = if options.exclude.is_empty() {
+        config.upgrade.exclude.unwrap_or_default()
+    } else {
+        options.exclude.clone()
+    };
+    let pinned = match options.pinned {
+        Some(pinned) => pinned,
+        None => config.upgrade.pinned.unwrap_or(false),
+    };
+    let mut manifests = ws
+        .select_members(&MemberFilter::default())?
+        .selected
+        .into_iter()
+        .map(|p| {
+            (
+                Some(p.name.clone()),
+                p.manifest_path.as_std_path().to_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if !manifests.iter().any(|(_, p)| *p == root_manifest_path) {
+        manifests.insert(0, (None, root_manifest_path.clone()));
+    }
+
+    let selected_dependencies = options
+        .packages
+        .iter()
+        .map(|name| {
+            let spec = CrateSpec::resolve(name)?;
+            if !spec.features.is_empty() || spec.source.is_some() {
+                anyhow::bail!("`{name}` can only name a crate and a version to upgrade to");
+            }
+            Ok((spec.name, spec.version_req))
+        })
+        .collect::<CargoResult<IndexMap<_, Option<_>>>>()?;
+    let mut processed_keys = BTreeSet::new();
+
+    let mut updated_registries = BTreeSet::new();
+    let mut sources = VersionSources::new(options.refresh);
+    let mut modified_crates = BTreeSet::new();
+    let mut git_crates = BTreeSet::new();
+    for (package, manifest_path) in &manifests {
+        let owner = package.as_deref().unwrap_or("workspace");
+        let mut manifest = LocalManifest::try_new(manifest_path)?;
+        let mut crate_modified = false;
+        let mut checked = Vec::new();
+        on_event(&UpgradeEvent::Checking {
+            package: package.clone(),
+            manifest_path: manifest_path.clone(),
+        })?;
+        for mut dep_table in manifest.dependency_tables_mut() {
+            if dep_table.patch_source().is_some() {
+                continue;
+            }
+            let dep_keys = dep_table
+                .table()
+                .iter()
+                .map(|(dep_key, _)| dep_key.to_owned())
+                .collect::<Vec<_>>();
+            for dep_key in &dep_keys {
+                let mut reason = None;
+
+                let dep_item = dep_table.table().get(dep_key).expect("key was just listed");
+                let dependency = match Dependency::from_toml(manifest_path, dep_key, dep_item) {
+                    Ok(dependency) => dependency,
+                    Err(err) => {
+                        on_event(&UpgradeEvent::UnsupportedEntry {
+                            key: dep_key.clone(),
+                            error: err.to_string(),
+                        })?;
+                        continue;
+                    }
+                };
+                processed_keys.insert(dependency.name.clone());
+                if !selected_dependencies.is_empty()
+                    && !selected_dependencies.contains_key(&dependency.name)
+                {
+                    reason.get_or_insert(UpgradeReason::Excluded);
+                }
+                if exclude
+                    .iter()
+                    .any(|pattern| matches_glob(pattern, &dependency.name))
+                {
+                    reason.get_or_insert(UpgradeReason::Excluded);
+                }
+                let old_version_req = match dependency.version() {
+                    Some(version_req) => version_req.to_owned(),
+                    None => {
+                        let maybe_reason = match dependency.source() {
+                            Some(Source::Git(_)) => {
+                                git_crates.insert(dependency.name.clone());
+                                Some(UpgradeReason::GitSource)
+                            }
+                            Some(Source::Path(_)) => Some(UpgradeReason::PathSource),
+                            Some(Source::Workspace(_)) | Some(Source::Registry(_)) | None => None,
+                        };
+                        if let Some(maybe_reason) = maybe_reason {
+                            reason.get_or_insert(maybe_reason);
+                            checked.push(DependencyUpgrade {
+                                package: owner.to_owned(),
+                                manifest_path: manifest_path.clone(),
+                                name: dependency.name.clone(),
+                                rename: dependency.rename.clone(),
+                                old_version_req: None,
+                                compatible_version: None,
+                                latest_version: None,
+                                new_version_req: None,
+                                reason,
+                            });
+                        } else {
+                            let source = dependency
+                                .source()
+                                .map(|s| s.to_string())
+                                .unwrap_or_else(|| "unknown".to_owned());
+                            on_event(&UpgradeEvent::UnknownSource {
+                                key: dependency.toml_key().to_owned(),
+                                source,
+                            })?;
+                        }
+                        continue;
+                    }
+                };
+
+                let (latest_compatible, latest_incompatible) = if dependency
+                    .source
+                    .as_ref()
+                    .and_then(|s| s.as_registry())
+                    .is_some()
+                {
+                    // Update indices for any alternative registries, unless
+                    // we're offline.
+                    let registry_url = dependency
+                        .registry()
+                        .map(|registry| registry_url(manifest_path, Some(registry)))
+                        .transpose()?;
+                    if !options.offline() {
+                        if let Some(registry_url) = &registry_url {
+                            if updated_registries.insert(registry_url.to_owned()) {
+                                update_registry_index_with(registry_url, |update| {
+                                    on_event(&UpgradeEvent::Index(update.clone()))
+                                })?;
+                            }
+                        }
+                    }
+                    let source = match registry_url
+                        .or_else(|| crate::registry_url(manifest_path, None).ok())
+                    {
+                        Some(registry_url) => Some(sources.get(registry_url)?),
+                        None => None,
+                    };
+                    let latest_compatible = VersionReq::parse(&old_version_req)
+                        .ok()
+                        .zip(source)
+                        .and_then(|(old_version_req, source)| {
+                            get_compatible_dependency_from(
+                                source,
+                                &dependency.name,
+                                &old_version_req,
+                            )
+                            .ok()
+                        })
+                        .map(|d| {
+                            d.version()
+                                .expect("registry packages always have a version")
+                                .to_owned()
+                        });
+                    let is_prerelease = old_version_req.contains('-');
+                    let latest_version = source
+                        .and_then(|source| {
+                            get_latest_dependency_from(source, &dependency.name, is_prerelease).ok()
+                        })
+                        .map(|d| {
+                            d.version()
+                                .expect("registry packages always have a version")
+                                .to_owned()
+                        });
+                    let latest_incompatible = if latest_version != latest_compatible {
+                        latest_version
+                    } else {
+                        // Its compatible
+                        None
+                    };
+                    (latest_compatible, latest_incompatible)
+                } else {
+                    (None, None)
+                };
+
+                let is_pinned_dep = dependency.rename.is_some() || is_pinned_req(&old_version_req);
+
+                let mut new_version_req = if reason.is_some() {
+                    Some(old_version_req.clone())
+                } else {
+                    None
+                };
+
+                if new_version_req.is_none() {
+                    if let Some(Some(explicit_version_req)) =
+                        selected_dependencies.get(&dependency.name)
+                    {
+                        if is_pinned_dep && !pinned {
+                            // `--pinned` is required in case the user meant an unpinned version
+                            // in the dependency tree
+                            reason.get_or_insert(UpgradeReason::Pinned);
+                        } else {
+                            new_version_req = Some(explicit_version_req.to_owned())
+                        }
+                    }
+                }
+
+                if new_version_req.is_none() {
+                    if let Some(latest_incompatible) = &latest_incompatible {
+                        let new_version: semver::Version = latest_incompatible.parse()?;
+                        let req_candidate =
+                            match upgrade_requirement(&old_version_req, &new_version) {
+                                Ok(Some(version_req)) => Some(version_req),
+                                Err(_) => {
+                                    // Didn't know how to preserve existing format, so abandon it
+                                    Some(latest_incompatible.clone())
+                                }
+                                _ => {
+                                    // Already at latest
+                                    None
+                                }
+                            };
+
+                        if req_candidate.is_some() {
+                            if is_pinned_dep && !pinned {
+                                // `--pinned` is required for incompatible upgrades
+                                reason.get_or_insert(UpgradeReason::Pinned);
+                            } else if !options.incompatible && !is_pinned_dep {
+                                // `--incompatible` is required for non-pinned deps
+                                reason.get_or_insert(UpgradeReason::Incompatible);
+                            } else {
+                                new_version_req = req_candidate;
+                            }
+                        }
+                    }
+                }
+
+                if new_version_req.is_none() {
+                    if let Some(latest_compatible) = &latest_compatible {
+                        // Compatible upgrades are allowed for pinned
+                        let new_version: semver::Version = latest_compatible.parse()?;
+                        let req_candidate =
+                            match upgrade_requirement(&old_version_req, &new_version) {
+                                Ok(Some(version_req)) => Some(version_req),
+                                Err(_) => {
+                                    // Do not change syntax for compatible upgrades
+                                    Some(old_version_req.clone())
+                                }
+                                _ => {
+                                    // Already at latest
+                                    None
+                                }
+                            };
+
+                        if req_candidate.is_some() {
+                            if !options.compatible {
+                                reason.get_or_insert(UpgradeReason::Compatible);
+                            } else {
+                                new_version_req = req_candidate;
+                            }
+                        }
+                    }
+                }
+
+                let new_version_req = new_version_req.unwrap_or_else(|| old_version_req.clone());
+
+                if new_version_req == old_version_req {
+                    reason.get_or_insert(UpgradeReason::Unchanged);
+                } else {
+                    let upgraded = dependency.clone().set_version(&new_version_req);
+                    dep_table.upsert(&upgraded, manifest_path);
+                    report.changes.push(ManifestChange {
+                        package: owner.to_owned(),
+                        manifest_path: manifest_path.clone(),
+                        kind: ChangeKind::DependencyRequirement {
+                            table: dep_table.path().to_vec(),
+                            dependency: dep_key.clone(),
+                        },
+                        old: old_version_req.clone(),
+                        new: new_version_req.clone(),
+                    });
+                    crate_modified = true;
+                    modified_crates.insert(dependency.name.clone());
+                }
+
+                let compatible_version = latest_compatible;
+                let latest_version = latest_incompatible.or_else(|| compatible_version.clone());
+                checked.push(DependencyUpgrade {
+                    package: owner.to_owned(),
+                    manifest_path: manifest_path.clone(),
+                    name: dependency.name.clone(),
+                    rename: dependency.rename.clone(),
+                    old_version_req: Some(old_version_req),
+                    compatible_version,
+                    latest_version,
+                    new_version_req: Some(new_version_req),
+                    reason,
+                });
+            }
+        }
+        on_event(&UpgradeEvent::Checked {
+            package: package.clone(),
+            dependencies: checked.clone(),
+        })?;
+        report.dependencies.extend(checked);
+        if !options.dry_run && !options.locked() && crate_modified {
+            manifest.write()?;
+        }
+    }
+
+    if !modified_crates.is_empty() && !options.dry_run {
+        if options.locked() {
+            let flag = if options.frozen {
+                "--frozen"
+            } else {
+                "--locked"
+            };
+            anyhow::bail!("cannot upgrade due to `{flag}`");
+        } else {
+            // Ensure lock file is updated and collect data for `recursive`
+            let metadata = WorkspaceMetadata::load(
+                Some(&root_manifest_path),
+                options.locked(),
+                options.offline(),
+            )?;
+            let mut locked = metadata.into_metadata().packages;
+
+            let precise_deps = selected_dependencies
+                .iter()
+                .filter_map(|(name, req)| {
+                    req.as_ref()
+                        .and_then(|req| VersionReq::parse(req).ok())
+                        .and_then(|req| {
+                            let precise = precise_version(&req)?;
+                            Some((name, (req, precise)))
+                        })
+                })
+                .collect::<BTreeMap<_, _>>();
+            if !precise_deps.is_empty() {
+                // Rollback the updates to the precise version
+                //
+                // Reusing updates (resolve_ws) so we know what lock_version to reference
+                for (name, (req, precise)) in &precise_deps {
+                    #[allow(clippy::unnecessary_lazy_evaluations)] // requires 1.62
+                    for lock_version in locked
+                        .iter()
+                        .filter(|p| p.name == **name)
+                        .map(|p| &p.version)
+                        .filter_map(|v| req.matches(v).then(|| v))
+                    {
+                        let mut cmd = Command::new("cargo");
+                        cmd.arg("update");
+                        cmd.arg("--manifest-path").arg(&root_manifest_path);
+                        if options.locked() {
+                            cmd.arg("--locked");
+                        }
+                        // NOTE: This will skip the official recursive check and we don't
+                        // recursively update its dependencies
+                        let dep = format!("{name}@{lock_version}");
+                        cmd.arg("--precise").arg(precise);
+                        cmd.arg("--package").arg(dep);
+                        // If we're going to request an update, it would have already been done by now
+                        cmd.arg("--offline");
+                        let output = cmd.output().context("failed to lock to precise version")?;
+                        if !output.status.success() {
+                            return Err(anyhow::format_err!(
+                                "{}",
+                                String::from_utf8_lossy(&output.stderr)
+                            ))
+                            .context("failed to lock to precise version");
+                        }
+                    }
+                }
+
+                // Update data for `recursive` with precise_deps
+                let offline = true; // index should already be updated
+                let metadata =
+                    WorkspaceMetadata::load(Some(&root_manifest_path), options.locked(), offline)?;
+                locked = metadata.into_metadata().packages;
+            }
+
+            if !git_crates.is_empty() && options.compatible {
+                on_event(&UpgradeEvent::UpgradingGitDependencies)?;
+                let mut cmd = Command::new("cargo");
+                cmd.arg("update");
+                cmd.arg("--manifest-path").arg(&root_manifest_path);
+                if options.locked() {
+                    cmd.arg("--locked");
+                }
+                for dep in git_crates.iter() {
+                    for lock_version in locked
+                        .iter()
+                        .filter(|p| {
+                            p.name == *dep
+                                && p.source
+                                    .as_ref()
+                                    .map(|s| s.repr.starts_with("git+"))
+                                    .unwrap_or(false)
+                        })
+                        .map(|p| &p.version)
+                    {
+                        let dep = format!("{dep}@{lock_version}");
+                        cmd.arg("--package").arg(dep);
+                    }
+                }
+                // If we're going to request an update, it would have already been done by now
+                cmd.arg("--offline");
+                cargo_update(&mut cmd, &mut on_event)?;
+
+                // Update data for `recursive` with precise_deps
+                let offline = true; // index should already be updated
+                let metadata =
+                    WorkspaceMetadata::load(Some(&root_manifest_path), options.locked(), offline)?;
+                locked = metadata.into_metadata().packages;
+            }
+
+            if options.recursive {
+                on_event(&UpgradeEvent::UpgradingRecursiveDependencies)?;
+                let mut cmd = Command::new("cargo");
+                cmd.arg("update");
+                cmd.arg("--manifest-path").arg(&root_manifest_path);
+                if options.locked() {
+                    cmd.arg("--locked");
+                }
+                // Limit recursive update to what we touched
+                cmd.arg("--aggressive");
+                let mut still_run = false;
+                for dep in modified_crates
+                    .iter()
+                    // Already updated so avoid discarding the precise version selection
+                    .filter(|c| !precise_deps.contains_key(c))
+                {
+                    for lock_version in locked.iter().filter(|p| p.name == *dep).map(|p| &p.version)
+                    {
+                        let dep = format!("{dep}@{lock_version}");
+                        cmd.arg("--package").arg(dep);
+                        still_run = true;
+                    }
+                }
+                // If we're going to request an update, it would have already been done by now
+                cmd.arg("--offline");
+                if still_run {
+                    cargo_update(&mut cmd, &mut on_event)?;
+                }
+            }
+        }
+    }
+
+    let unused = selected_dependencies
+        .keys()
+        .filter(|k| !processed_keys.contains(k.as_str()))
+        .map(|k| k.as_str())
+        .collect::<Vec<_>>();
+    match unused.len() {
+        0 => {}
+        1 => {
+            return Err(ErrorKind::NothingToDo.attach(anyhow::format_err!(
+                "dependency {} doesn't exist",
+                unused.join(", ")
+            )))
+        }
+        _ => {
+            return Err(ErrorKind::NothingToDo.attach(anyhow::format_err!(
+                "dependencies {} don't exist",
+                unused.join(", ")
+            )))
+        }
+    }
+
+    Ok(report)
+}
+
+/// Run a `cargo update` of the recursive dependency update
+///
+/// Its output is passed on to `on_event` rather than printed.
+fn cargo_update(
+    cmd: &mut Command,
+    on_event: &mut dyn FnMut(&UpgradeEvent) -> CargoResult<()>,
+) -> CargoResult<()> {
+    let output = cmd.output().context("recursive dependency update failed")?;
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        return Err(anyhow::format_err!("{}", stderr))
+            .context("recursive dependency update failed");
+    }
+    on_event(&UpgradeEvent::LockfileUpdated { output: stderr })
+}
+
+/// Where versions are looked up, opened once for each registry
+struct VersionSources {
+    sources: BTreeMap<Url, Box<dyn VersionSource>>,
+    /// Whether to bypass the versions cached by earlier commands
+    refresh: bool,
+}
+
+impl VersionSources {
+    fn new(refresh: bool) -> Self {
+        Self {
+            sources: BTreeMap::new(),
+            refresh,
+        }
+    }
+
+    /// The source for `registry`, opening it on first use
+    ///
+    /// A registry that can't be opened fails the upgrade, rather than looking like it has no
+    /// versions.
+    fn get(&mut self, registry: Url) -> CargoResult<&dyn VersionSource> {
+        let refresh = self.refresh;
+        let source = match self.sources.entry(registry) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let source = registry_source_with(entry.key(), refresh)
+                    .with_context(|| format!("Failed to open registry '{}'", entry.key()))?;
+                entry.insert(source)
+            }
+        };
+        Ok(source.as_ref())
+    }
+}
+
+fn is_pinned_req(old_version_req: &str) -> bool {
+    if let Ok(version_req) = VersionReq::parse(old_version_req) {
+        version_req.comparators.iter().any(|comparator| {
+            matches!(
+                comparator.op,
+                Op::Exact | Op::Less | Op::LessEq | Op::Wildcard
+            )
+        })
+    } else {
+        false
+    }
+}
+
+fn precise_version(version_req: &VersionReq) -> Option<String> {
+    version_req
+        .comparators
+        .iter()
+        .filter(|c| {
+            matches!(
+                c.op,
+                // Only ops we can determine a precise version from
+                Op::Exact | Op::GreaterEq | Op::LessEq | Op::Tilde | Op::Caret | Op::Wildcard
+            )
+        })
+        .filter_map(|c| {
+            // Only do it when full precision is specified
+            c.minor.and_then(|minor| {
+                c.patch.map(|patch| semver::Version {
+                    major: c.major,
+                    minor,
+                    patch,
+                    pre: c.pre.clone(),
+                    build: Default::default(),
+                })
+            })
+        })
+        .max()
+        .map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exact_is_pinned_req() {
+        let req = "=3";
+        assert!(is_pinned_req(req));
+    }
+
+    #[test]
+    fn less_than_is_pinned_req() {
+        let req = "<3";
+        assert!(is_pinned_req(req));
+    }
+
+    #[test]
+    fn less_than_equal_is_pinned_req() {
+        let req = "<=3";
+        assert!(is_pinned_req(req));
+    }
+
+    #[test]
+    fn minor_wildcard_is_pinned_req() {
+        let req = "3.*";
+        assert!(is_pinned_req(req));
+    }
+
+    #[test]
+    fn major_wildcard_is_not_pinned() {
+        let req = "*";
+        assert!(!is_pinned_req(req));
+    }
+
+    #[test]
+    fn greater_than_is_not_pinned() {
+        let req = ">3";
+        assert!(!is_pinned_req(req));
+    }
+
+    #[test]
+    fn greater_than_equal_is_not_pinned() {
+        let req = ">=3";
+        assert!(!is_pinned_req(req));
+    }
+
+    #[test]
+    fn caret_is_not_pinned() {
+        let req = "^3";
+        assert!(!is_pinned_req(req));
+    }
+
+    #[test]
+    fn default_is_not_pinned() {
+        let req = "3";
+        assert!(!is_pinned_req(req));
+    }
+}
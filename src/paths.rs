@@ -0,0 +1,75 @@
+//! Resolving the paths of path dependencies, so manifests can be edited away from the filesystem.
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves the paths of path dependencies to tell whether they are the same
+///
+/// Editing only needs this to match up spellings of a path like `../foo` and `./../foo`, e.g. to
+/// find the dependents of a package.  [`FsResolver`] asks the filesystem, [`LexicalResolver`]
+/// works on manifests that were never checked out.
+pub trait PathResolver {
+    /// Canonical form of the absolute `path`, or `None` if it can't be resolved
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf>;
+
+    /// Whether `a` and `b` are the same path
+    fn same_path(&self, a: &Path, b: &Path) -> bool {
+        if a.components().eq(b.components()) {
+            return true;
+        }
+        match (self.canonicalize(a), self.canonicalize(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Resolves paths on the local filesystem, following symlinks
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FsResolver;
+
+impl PathResolver for FsResolver {
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        dunce::canonicalize(path).ok()
+    }
+}
+
+/// Resolves paths by dropping their `.` and `..` components, without a filesystem
+///
+/// Symlinks are not followed, as there are none to follow in manifests fetched over the network.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LexicalResolver;
+
+impl PathResolver for LexicalResolver {
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    // Going above the root
+                    if !normalized.pop() {
+                        return None;
+                    }
+                }
+                component => normalized.push(component),
+            }
+        }
+        Some(normalized)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lexical() {
+        let resolver = LexicalResolver;
+        assert_eq!(
+            resolver.canonicalize(Path::new("/ws/crates/net/./../dns")),
+            Some(PathBuf::from("/ws/crates/dns"))
+        );
+        assert!(resolver.same_path(Path::new("/ws/net/../dns"), Path::new("/ws/./dns")));
+        assert!(!resolver.same_path(Path::new("/ws/net"), Path::new("/ws/dns")));
+        assert_eq!(resolver.canonicalize(Path::new("/..")), None);
+    }
+}
@@ -1,6 +1,7 @@
 use super::errors::*;
+use super::local_source::LocalSource;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use url::Url;
 
 const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
@@ -8,21 +9,38 @@ const CRATES_IO_REGISTRY: &str = "crates-io";
 
 /// Find the URL of a registry
 pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult<Url> {
-    // TODO support local registry sources, directory sources, git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
+    // TODO support git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
     fn read_config(
         registries: &mut HashMap<String, Source>,
         path: impl AsRef<Path>,
     ) -> CargoResult<()> {
         // TODO unit test for source replacement
+        let path = path.as_ref();
+        trace!("reading cargo config {}", path.display());
         let content = std::fs::read_to_string(path)?;
         let config = toml::from_str::<CargoConfig>(&content).map_err(|_| invalid_cargo_config())?;
         for (key, value) in config.registries {
             registries.entry(key).or_insert(Source {
                 registry: value.index,
-                replace_with: None,
+                ..Default::default()
             });
         }
-        for (key, value) in config.source {
+        // Paths are relative to the directory containing the `.cargo` directory
+        let config_root = match path.parent().and_then(Path::parent) {
+            Some(config_root) => config_root,
+            None if config.source.is_empty() => Path::new(""),
+            None => anyhow::bail!(
+                "Unable to resolve the sources in cargo config {}, which is not in a `.cargo` directory",
+                path.display()
+            ),
+        };
+        for (key, mut value) in config.source {
+            value.directory = value
+                .directory
+                .map(|p| config_root.join(p).display().to_string());
+            value.local_registry = value
+                .local_registry
+                .map(|p| config_root.join(p).display().to_string());
             registries.entry(key).or_insert(value);
         }
         Ok(())
@@ -31,33 +49,8 @@ pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult
     // it's looks like a singly linked list
     // put relations in this map.
     let mut registries: HashMap<String, Source> = HashMap::new();
-    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
-    for work_dir in manifest_path
-        .parent()
-        .expect("there must be a parent directory")
-        .ancestors()
-    {
-        let work_cargo_dir = work_dir.join(".cargo");
-        let config_path = work_cargo_dir.join("config");
-        if config_path.is_file() {
-            read_config(&mut registries, config_path)?;
-        } else {
-            let config_path = work_cargo_dir.join("config.toml");
-            if config_path.is_file() {
-                read_config(&mut registries, config_path)?;
-            }
-        }
-    }
-
-    let default_cargo_home = home::cargo_home()?;
-    let default_config_path = default_cargo_home.join("config");
-    if default_config_path.is_file() {
-        read_config(&mut registries, default_config_path)?;
-    } else {
-        let default_config_path = default_cargo_home.join("config.toml");
-        if default_config_path.is_file() {
-            read_config(&mut registries, default_config_path)?;
-        }
+    for config_path in cargo_config_paths(manifest_path)? {
+        read_config(&mut registries, config_path)?;
     }
 
     // find head of the relevant linked list
@@ -77,6 +70,7 @@ pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult
     // search this linked list and find the tail
     while let Some(replace_with) = &source.replace_with {
         let is_crates_io = replace_with == CRATES_IO_INDEX;
+        debug!("source replaced with `{replace_with}`");
         source = registries.remove(replace_with).with_context(|| {
             anyhow::format_err!("The source '{}' could not be found", replace_with)
         })?;
@@ -87,14 +81,54 @@ pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult
         }
     }
 
+    let registry_name = registry.unwrap_or(CRATES_IO_REGISTRY);
+    if let Some(directory) = &source.directory {
+        debug!("registry `{registry_name}` is the directory {directory}");
+        return LocalSource::Directory(directory.into()).to_url();
+    }
+    if let Some(local_registry) = &source.local_registry {
+        debug!("registry `{registry_name}` is the local registry {local_registry}");
+        return LocalSource::LocalRegistry(local_registry.into()).to_url();
+    }
+
     let registry_url = source
         .registry
         .and_then(|x| Url::parse(&x).ok())
         .with_context(invalid_cargo_config)?;
+    debug!(
+        "registry `{registry_name}` is {}",
+        crate::trace::redact_url(&registry_url)
+    );
 
     Ok(registry_url)
 }
 
+/// Cargo's config files that apply to `manifest_path`, from the highest precedence to the lowest
+///
+/// ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
+fn cargo_config_paths(manifest_path: &Path) -> CargoResult<Vec<PathBuf>> {
+    fn config_in(cargo_dir: &Path) -> Option<PathBuf> {
+        ["config", "config.toml"]
+            .iter()
+            .map(|name| cargo_dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    let mut paths = manifest_path
+        .parent()
+        .expect("there must be a parent directory")
+        .ancestors()
+        .filter_map(|work_dir| config_in(&work_dir.join(".cargo")))
+        .collect::<Vec<_>>();
+    let default_cargo_home = home::cargo_home()?;
+    if let Some(path) = config_in(&default_cargo_home) {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
 #[derive(Debug, Deserialize)]
 struct CargoConfig {
     #[serde(default)]
@@ -108,6 +142,9 @@ struct Source {
     #[serde(rename = "replace-with")]
     replace_with: Option<String>,
     registry: Option<String>,
+    directory: Option<String>,
+    #[serde(rename = "local-registry")]
+    local_registry: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
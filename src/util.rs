@@ -1,10 +1,65 @@
-use std::io::Write;
+use std::io::{IsTerminal as _, Write};
 
 pub use termcolor::{Color, ColorChoice};
 use termcolor::{ColorSpec, StandardStream, WriteColor};
 
 use crate::{CargoResult, Context};
 
+/// When to color output, e.g. from `--color`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorWhen {
+    /// Color streams that are terminals, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl std::str::FromStr for ColorWhen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(String::from("[valid values: auto, always, never]")),
+        }
+    }
+}
+
+/// Decide whether to color output, given the `--color` flag
+///
+/// As with cargo, the flag takes precedence over `CARGO_TERM_COLOR`.  When coloring
+/// automatically, `stdout` and `stderr` are only colored if they are terminals and `NO_COLOR`
+/// isn't set.  Applies to all the `shell_*` functions for the rest of the process.
+pub fn set_color(flag: Option<ColorWhen>) -> CargoResult<()> {
+    let env = std::env::var("CARGO_TERM_COLOR").ok();
+    let choice = match color_when(flag, env.as_deref())? {
+        ColorWhen::Auto => concolor_control::ColorChoice::Auto,
+        ColorWhen::Always => concolor_control::ColorChoice::Always,
+        ColorWhen::Never => concolor_control::ColorChoice::Never,
+    };
+    concolor_control::set(choice);
+    Ok(())
+}
+
+fn color_when(flag: Option<ColorWhen>, env: Option<&str>) -> CargoResult<ColorWhen> {
+    if let Some(flag) = flag {
+        return Ok(flag);
+    }
+    match env {
+        Some(value) => value.parse().map_err(|_| {
+            anyhow::format_err!(
+                "`CARGO_TERM_COLOR` must be `auto`, `always`, or `never`, but found `{value}`"
+            )
+        }),
+        None => Ok(ColorWhen::Auto),
+    }
+}
+
 /// Whether to color logged output
 pub fn colorize_stderr() -> ColorChoice {
     if concolor_control::get(concolor_control::Stream::Stderr).color() {
@@ -79,3 +134,270 @@ pub fn shell_write_stdout(fragment: impl std::fmt::Display, spec: &ColorSpec) ->
     output.reset()?;
     Ok(())
 }
+
+/// Narrowest a column is shrunk to, unless its header is wider
+const MIN_SHRUNK_WIDTH: usize = 8;
+
+/// Rows of cells printed in columns, see [`Table::print`]
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<(String, ColorSpec)>>,
+    /// Columns that may be shortened to fit the terminal
+    shrinkable: Vec<bool>,
+    plain: bool,
+}
+
+/// How a [`Table`] lays out its columns
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Layout {
+    /// Padded to line up, and fitting in this many characters if known
+    Aligned(Option<usize>),
+    /// Separated by tabs, for other programs to read
+    Plain,
+}
+
+impl Table {
+    /// A table with a column for each of `header`
+    pub fn new(header: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(|name| (*name).to_owned()).collect(),
+            rows: Vec::new(),
+            shrinkable: vec![false; header.len()],
+            plain: false,
+        }
+    }
+
+    /// Shorten `column` in the middle, with an ellipsis, when the table is too wide for the
+    /// terminal
+    pub fn shrink(mut self, column: usize) -> Self {
+        self.shrinkable[column] = true;
+        self
+    }
+
+    /// Separate the columns with tabs even on a terminal, e.g. for `--message-format json`
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// Add a row, with a cell and its color for each column
+    pub fn push(&mut self, row: Vec<(String, ColorSpec)>) {
+        debug_assert_eq!(row.len(), self.header.len());
+        self.rows.push(row);
+    }
+
+    /// Print to `stdout`, lined up within the width of the terminal
+    ///
+    /// When `stdout` isn't a terminal, the columns are separated by tabs instead.
+    pub fn print(&self) -> CargoResult<()> {
+        let layout = if self.plain || !std::io::stdout().is_terminal() {
+            Layout::Plain
+        } else {
+            Layout::Aligned(terminal_size::terminal_size().map(|(width, _)| usize::from(width.0)))
+        };
+        for line in self.lines(layout) {
+            for (fragment, spec) in line {
+                shell_write_stdout(fragment, &spec)?;
+            }
+            shell_write_stdout("\n", &ColorSpec::new())?;
+        }
+        Ok(())
+    }
+
+    fn lines(&self, layout: Layout) -> Vec<Vec<(String, ColorSpec)>> {
+        let mut header_spec = ColorSpec::new();
+        header_spec.set_bold(true);
+        let header = self
+            .header
+            .iter()
+            .map(|name| (name.clone(), header_spec.clone()))
+            .collect::<Vec<_>>();
+        let max_width = match layout {
+            Layout::Aligned(max_width) => max_width,
+            Layout::Plain => {
+                return std::iter::once(header)
+                    .chain(self.rows.iter().cloned())
+                    .map(|row| {
+                        let mut line = Vec::new();
+                        for (i, cell) in row.into_iter().enumerate() {
+                            if 0 < i {
+                                line.push(("\t".to_owned(), ColorSpec::new()));
+                            }
+                            line.push(cell);
+                        }
+                        line
+                    })
+                    .collect();
+            }
+        };
+
+        let underline = self
+            .header
+            .iter()
+            .map(|name| ("=".repeat(text_width(name)), header_spec.clone()))
+            .collect::<Vec<_>>();
+        let rows = [header, underline]
+            .into_iter()
+            .chain(self.rows.iter().cloned())
+            .collect::<Vec<_>>();
+        let mut widths = vec![0; self.header.len()];
+        for row in &rows {
+            for (width, (text, _)) in widths.iter_mut().zip(row) {
+                *width = (*width).max(text_width(text));
+            }
+        }
+        if let Some(max_width) = max_width {
+            self.fit(&mut widths, max_width);
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                let last = row.len().saturating_sub(1);
+                let mut line = Vec::new();
+                for (i, (text, spec)) in row.into_iter().enumerate() {
+                    if 0 < i {
+                        line.push((" ".to_owned(), ColorSpec::new()));
+                    }
+                    let text = truncate_middle(&text, widths[i]);
+                    let padding = widths[i] - text_width(&text);
+                    line.push((text, spec));
+                    if i < last && 0 < padding {
+                        line.push((" ".repeat(padding), ColorSpec::new()));
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Narrow the widest shrinkable column, one character at a time, until the table is at
+    /// most `max_width` wide
+    fn fit(&self, widths: &mut [usize], max_width: usize) {
+        let separators = widths.len().saturating_sub(1);
+        while max_width < widths.iter().sum::<usize>() + separators {
+            let widest = widths
+                .iter()
+                .enumerate()
+                .filter(|(i, width)| {
+                    let min = text_width(&self.header[*i]).max(MIN_SHRUNK_WIDTH);
+                    self.shrinkable[*i] && min < **width
+                })
+                .max_by_key(|(_, width)| **width)
+                .map(|(i, _)| i);
+            match widest {
+                Some(i) => widths[i] -= 1,
+                // Wider than the terminal it is, then
+                None => break,
+            }
+        }
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// `text` shortened to `width` characters by replacing its middle with `…`
+fn truncate_middle(text: &str, width: usize) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    if chars.len() <= width {
+        return text.to_owned();
+    }
+    let kept = width.saturating_sub(1);
+    let tail = kept / 2;
+    let head = kept - tail;
+    chars[..head]
+        .iter()
+        .chain(['…'].iter())
+        .chain(&chars[chars.len() - tail..])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn color_precedence() {
+        assert_eq!(color_when(None, None).unwrap(), ColorWhen::Auto);
+        assert_eq!(color_when(None, Some("never")).unwrap(), ColorWhen::Never);
+        assert_eq!(
+            color_when(Some(ColorWhen::Always), Some("never")).unwrap(),
+            ColorWhen::Always
+        );
+        assert_eq!(
+            color_when(Some(ColorWhen::Never), Some("bogus")).unwrap(),
+            ColorWhen::Never
+        );
+        assert!(color_when(None, Some("bogus")).is_err());
+    }
+
+    fn render(table: &Table, layout: Layout) -> String {
+        table
+            .lines(layout)
+            .into_iter()
+            .map(|line| line.into_iter().map(|(text, _)| text).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn table() -> Table {
+        let mut table = Table::new(&["name", "old", "new"]).shrink(0);
+        for (name, old, new) in [
+            ("serde", "1.0", "1.0.152"),
+            ("workspace-member-with-a-long-name", "0.1", "0.2"),
+        ] {
+            table.push(
+                [name, old, new]
+                    .into_iter()
+                    .map(|cell| (cell.to_owned(), ColorSpec::new()))
+                    .collect(),
+            );
+        }
+        table
+    }
+
+    #[test]
+    fn table_aligned() {
+        assert_eq!(
+            render(&table(), Layout::Aligned(None)),
+            "\
+name                              old new
+====                              === ===
+serde                             1.0 1.0.152
+workspace-member-with-a-long-name 0.1 0.2"
+        );
+    }
+
+    #[test]
+    fn table_fits_terminal() {
+        assert_eq!(
+            render(&table(), Layout::Aligned(Some(24))),
+            "\
+name         old new
+====         === ===
+serde        1.0 1.0.152
+worksp…-name 0.1 0.2"
+        );
+
+        // Columns are not shrunk past their header
+        let narrow = render(&table(), Layout::Aligned(Some(10)));
+        assert!(narrow.ends_with("\nwork…ame 0.1 0.2"), "{narrow}");
+    }
+
+    #[test]
+    fn table_plain() {
+        assert_eq!(
+            render(&table(), Layout::Plain),
+            "name\told\tnew\nserde\t1.0\t1.0.152\nworkspace-member-with-a-long-name\t0.1\t0.2"
+        );
+    }
+
+    #[test]
+    fn truncates_middle() {
+        assert_eq!(truncate_middle("cargo-edit", 20), "cargo-edit");
+        assert_eq!(truncate_middle("cargo-edit", 6), "car…it");
+        assert_eq!(truncate_middle("cargo-edit", 5), "ca…it");
+    }
+}
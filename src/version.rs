@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::str::FromStr;
 
 use super::errors::*;
@@ -131,7 +132,7 @@ fn prerelease_id_version(version: &semver::Version) -> CargoResult<Option<(Strin
         if let Some((alpha, numeric)) = version.pre.as_str().split_once('.') {
             let alpha = alpha.to_owned();
             let numeric = u64::from_str(numeric)
-                .map_err(|_| anyhow::format_err!("This version scheme is not supported. Use format like `pre`, `dev` or `alpha.1` for prerelease symbol"))?;
+                .map_err(|_| ErrorKind::InvalidVersion.attach(anyhow::format_err!("This version scheme is not supported. Use format like `pre`, `dev` or `alpha.1` for prerelease symbol")))?;
             Ok(Some((alpha, Some(numeric))))
         } else {
             Ok(Some((version.pre.as_str().to_owned(), None)))
@@ -141,7 +142,157 @@ fn prerelease_id_version(version: &semver::Version) -> CargoResult<Option<(Strin
     }
 }
 
+/// Version to change a package to
+#[derive(Clone, Debug)]
+pub enum TargetVersion {
+    /// Bump the current version
+    Relative(BumpLevel),
+    /// Change to this version, which can't be lower than the current one
+    Absolute(semver::Version),
+}
+
+impl TargetVersion {
+    /// Version to change `current` to, if it changes at all
+    pub fn bump(
+        &self,
+        current: &semver::Version,
+        metadata: Option<&str>,
+    ) -> CargoResult<Option<semver::Version>> {
+        match self {
+            TargetVersion::Relative(bump_level) => {
+                let mut potential_version = current.to_owned();
+                bump_level.bump_version(&mut potential_version, metadata)?;
+                if potential_version != *current {
+                    let version = potential_version;
+                    Ok(Some(version))
+                } else {
+                    Ok(None)
+                }
+            }
+            TargetVersion::Absolute(version) => {
+                if current < version {
+                    let mut version = version.clone();
+                    if version.build.is_empty() {
+                        if let Some(metadata) = metadata {
+                            version.build = semver::BuildMetadata::new(metadata)?;
+                        } else {
+                            version.build = current.build.clone();
+                        }
+                    }
+
+                    Ok(Some(version))
+                } else if current == version {
+                    Ok(None)
+                } else {
+                    Err(version_downgrade_err(current, version))
+                }
+            }
+        }
+    }
+}
+
+impl Default for TargetVersion {
+    fn default() -> Self {
+        TargetVersion::Relative(BumpLevel::Release)
+    }
+}
+
+/// Part of the version to increment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BumpLevel {
+    /// Increment the major version
+    Major,
+    /// Increment the minor version
+    Minor,
+    /// Increment the patch version, or strip the pre-release of one
+    Patch,
+    /// Strip all pre-release flags
+    Release,
+    /// Increment the release candidate
+    Rc,
+    /// Increment the beta
+    Beta,
+    /// Increment the alpha
+    Alpha,
+}
+
+impl FromStr for BumpLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(BumpLevel::Major),
+            "minor" => Ok(BumpLevel::Minor),
+            "patch" => Ok(BumpLevel::Patch),
+            "release" => Ok(BumpLevel::Release),
+            "rc" => Ok(BumpLevel::Rc),
+            "beta" => Ok(BumpLevel::Beta),
+            "alpha" => Ok(BumpLevel::Alpha),
+            _ => Err(String::from(
+                "[valid values: major, minor, patch, rc, beta, alpha]",
+            )),
+        }
+    }
+}
+
+impl BumpLevel {
+    /// The version following `version` at this level
+    ///
+    /// Errors if a pre-release would go back a phase, e.g. bumping the alpha of a beta.
+    pub fn bump(self, version: &semver::Version) -> CargoResult<semver::Version> {
+        let mut next = version.clone();
+        self.bump_version(&mut next, None)?;
+        Ok(next)
+    }
+
+    /// Increment `version` in place, setting its metadata if given
+    pub fn bump_version(
+        self,
+        version: &mut semver::Version,
+        metadata: Option<&str>,
+    ) -> CargoResult<()> {
+        match self {
+            BumpLevel::Major => {
+                version.increment_major();
+            }
+            BumpLevel::Minor => {
+                version.increment_minor();
+            }
+            BumpLevel::Patch => {
+                if !version.is_prerelease() {
+                    version.increment_patch();
+                } else {
+                    version.pre = semver::Prerelease::EMPTY;
+                }
+            }
+            BumpLevel::Release => {
+                if version.is_prerelease() {
+                    version.pre = semver::Prerelease::EMPTY;
+                }
+            }
+            BumpLevel::Rc => {
+                version.increment_rc()?;
+            }
+            BumpLevel::Beta => {
+                version.increment_beta()?;
+            }
+            BumpLevel::Alpha => {
+                version.increment_alpha()?;
+            }
+        };
+
+        if let Some(metadata) = metadata {
+            version.metadata(metadata)?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Upgrade an existing requirement to a new version
+///
+/// Requirements of several comparators with bounds, like `>=0.14, <0.17`, are only rewritten if
+/// they don't already admit `version`, see [`widen_comparator`].
 pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<Option<String>> {
     let req_text = req.to_string();
     let raw_req = semver::VersionReq::parse(&req_text)
@@ -149,6 +300,28 @@ pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<
     if raw_req.comparators.is_empty() {
         // Empty matches everything, no-change.
         Ok(None)
+    } else if raw_req.comparators.len() > 1 && raw_req.comparators.iter().any(|c| is_bound(c.op)) {
+        if raw_req.matches(version) {
+            return Ok(None);
+        }
+        let comparators: CargoResult<Vec<_>> = raw_req
+            .comparators
+            .into_iter()
+            .map(|p| widen_comparator(p, version))
+            .collect();
+        let new_req = semver::VersionReq {
+            comparators: comparators?,
+        };
+        if !new_req.matches(version) {
+            // Pre-releases are only matched by a comparator on the same release
+            return Err(unsupported_version_req(req));
+        }
+        let mut new_req_text = new_req.to_string();
+        if new_req_text.starts_with('^') && !req.starts_with('^') {
+            new_req_text.remove(0);
+        }
+        debug!("`{req}` widened to `{new_req_text}` for {version}");
+        Ok(Some(new_req_text))
     } else {
         let comparators: CargoResult<Vec<_>> = raw_req
             .comparators
@@ -173,6 +346,7 @@ pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<
         if new_req_text == req_text {
             Ok(None)
         } else {
+            debug!("`{req}` rewritten to `{new_req_text}` for {version}");
             Ok(Some(new_req_text))
         }
     }
@@ -207,6 +381,88 @@ fn set_comparator(
     }
 }
 
+fn is_bound(op: semver::Op) -> bool {
+    matches!(
+        op,
+        semver::Op::Greater | semver::Op::GreaterEq | semver::Op::Less | semver::Op::LessEq
+    )
+}
+
+/// Change a comparator of a requirement with bounds just enough to admit `version`
+///
+/// Upper bounds are raised, keeping their precision and whether they are inclusive, e.g. `<0.17`
+/// becomes `<0.18` for `0.17.2`.  Lower bounds are lowered to `version` if it is below them, which
+/// makes a `>` inclusive.  Other comparators are upgraded as in a requirement without bounds.
+fn widen_comparator(
+    mut pred: semver::Comparator,
+    version: &semver::Version,
+) -> CargoResult<semver::Comparator> {
+    if !is_bound(pred.op) {
+        let release = semver::Version::new(version.major, version.minor, version.patch);
+        let req = semver::VersionReq {
+            comparators: vec![pred.clone()],
+        };
+        if req.matches(&release) {
+            return Ok(pred);
+        }
+        return set_comparator(pred, version);
+    }
+
+    let ord = compare_to_comparator(version, &pred);
+    let admits = match pred.op {
+        semver::Op::Greater => ord.is_gt(),
+        semver::Op::GreaterEq => ord.is_ge(),
+        semver::Op::Less => ord.is_lt(),
+        semver::Op::LessEq => ord.is_le(),
+        _ => unreachable!("only bounds are compared"),
+    };
+    if admits {
+        return Ok(pred);
+    }
+
+    match pred.op {
+        semver::Op::Less => {
+            // Exclusive, so step past `version` at the precision of the bound
+            pred.pre = semver::Prerelease::EMPTY;
+            if pred.patch.is_some() {
+                pred.major = version.major;
+                pred.minor = Some(version.minor);
+                pred.patch = Some(version.patch + 1);
+            } else if pred.minor.is_some() {
+                pred.major = version.major;
+                pred.minor = Some(version.minor + 1);
+            } else {
+                pred.major = version.major + 1;
+            }
+            Ok(pred)
+        }
+        semver::Op::Greater => {
+            pred.op = semver::Op::GreaterEq;
+            Ok(assign_partial_req(version, pred))
+        }
+        _ => Ok(assign_partial_req(version, pred)),
+    }
+}
+
+/// Order `version` against the bound of `pred`, only looking as far as its precision
+fn compare_to_comparator(version: &semver::Version, pred: &semver::Comparator) -> Ordering {
+    let ord = version.major.cmp(&pred.major);
+    let ord = match pred.minor {
+        Some(minor) => ord.then(version.minor.cmp(&minor)),
+        None => return ord,
+    };
+    let ord = match pred.patch {
+        Some(patch) => ord.then(version.patch.cmp(&patch)),
+        None => return ord,
+    };
+    ord.then_with(|| match (version.pre.is_empty(), pred.pre.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => version.pre.cmp(&pred.pre),
+    })
+}
+
 fn assign_partial_req(
     version: &semver::Version,
     mut pred: semver::Comparator,
@@ -292,6 +548,74 @@ mod test {
         }
     }
 
+    mod bump {
+        use super::*;
+
+        fn version(version: &str) -> semver::Version {
+            semver::Version::parse(version).unwrap()
+        }
+
+        #[test]
+        fn levels() {
+            let cases = [
+                (BumpLevel::Major, "1.2.3", "2.0.0"),
+                (BumpLevel::Major, "1.2.3-rc.1+git.1", "2.0.0"),
+                (BumpLevel::Minor, "1.2.3", "1.3.0"),
+                (BumpLevel::Patch, "1.2.3", "1.2.4"),
+                (BumpLevel::Patch, "1.2.3-rc.1", "1.2.3"),
+                (BumpLevel::Release, "1.2.3", "1.2.3"),
+                (BumpLevel::Release, "1.2.3-beta.2", "1.2.3"),
+                (BumpLevel::Rc, "1.2.3", "1.2.4-rc.1"),
+                (BumpLevel::Rc, "1.2.3-beta.2", "1.2.3-rc.1"),
+                (BumpLevel::Beta, "1.2.3-alpha.1", "1.2.3-beta.1"),
+                (BumpLevel::Alpha, "1.2.3-alpha.1", "1.2.3-alpha.2"),
+            ];
+            for (level, current, expected) in cases {
+                let actual = level.bump(&version(current)).unwrap();
+                assert_eq!(actual, version(expected), "{level:?} from {current}");
+            }
+        }
+
+        #[test]
+        fn level_going_back_a_phase() {
+            assert!(BumpLevel::Beta.bump(&version("1.2.3-rc.1")).is_err());
+            assert!(BumpLevel::Alpha.bump(&version("1.2.3-beta.1")).is_err());
+        }
+
+        #[test]
+        fn relative() {
+            let target = TargetVersion::Relative(BumpLevel::Minor);
+            let actual = target.bump(&version("1.2.3"), Some("git.1")).unwrap();
+            assert_eq!(actual, Some(version("1.3.0+git.1")));
+
+            let target = TargetVersion::Relative(BumpLevel::Release);
+            assert_eq!(target.bump(&version("1.2.3"), None).unwrap(), None);
+        }
+
+        #[test]
+        fn absolute() {
+            let target = TargetVersion::Absolute(version("2.0.0"));
+            assert_eq!(
+                target.bump(&version("1.2.3+git.1"), None).unwrap(),
+                Some(version("2.0.0+git.1"))
+            );
+            assert_eq!(
+                target.bump(&version("1.2.3+git.1"), Some("git.2")).unwrap(),
+                Some(version("2.0.0+git.2"))
+            );
+            assert_eq!(target.bump(&version("2.0.0"), None).unwrap(), None);
+            assert!(target.bump(&version("2.0.1"), None).is_err());
+        }
+
+        #[test]
+        fn abs_bump_from_dev() {
+            let target = TargetVersion::Absolute(version("2022.3.0"));
+            let actual = target.bump(&version("2022.3.0-dev-12345"), None).unwrap();
+            let actual = actual.expect("Version changed").to_string();
+            assert_eq!(actual, "2022.3.0");
+        }
+    }
+
     mod upgrade_requirement {
         use super::*;
 
@@ -410,5 +734,60 @@ mod test {
             assert_req_bump("1.1.1", "=1.0.0", "=1.1.1");
             assert_req_bump("2.0.0", "=1.0.0", "=2.0.0");
         }
+
+        #[test]
+        fn bounds() {
+            let cases = [
+                // Exclusive upper bound
+                ("0.16.3", ">=0.14, <0.17", None),
+                ("0.17.2", ">=0.14, <0.17", Some(">=0.14, <0.18")),
+                ("1.2.0", ">=0.14, <0.17", Some(">=0.14, <1.3")),
+                ("0.17.2", ">=0.14.0, <0.17.0", Some(">=0.14.0, <0.17.3")),
+                ("2.5.0", ">=1, <2", Some(">=1, <3")),
+                // Inclusive upper bound
+                ("0.16.9", ">=0.14, <=0.16", None),
+                ("0.17.2", ">=0.14, <=0.16", Some(">=0.14, <=0.17")),
+                ("0.17.2", ">=0.14.0, <=0.16.5", Some(">=0.14.0, <=0.17.2")),
+                ("3.0.0", ">=1, <=2", Some(">=1, <=3")),
+                // Lower bound
+                ("0.13.1", ">=0.14, <0.17", Some(">=0.13, <0.17")),
+                ("0.14.2", ">0.14, <0.17", Some(">=0.14, <0.17")),
+                ("0.15.0", ">0.14, <0.17", None),
+                // Wildcard components
+                ("1.6.0", "1.*, <1.5", Some("1.*, <1.7")),
+                ("2.0.0", "1.*, <1.5", Some("2.*, <2.1")),
+                ("1.4.0", "1.*, <1.5", None),
+                ("1.3.0", "^1.2, <1.5", None),
+                ("1.5.1", "^1.2, <1.5", Some("^1.2, <1.6")),
+                // Pre-release boundaries
+                ("1.0.0-beta.2", ">=1.0.0-alpha.1, <1.0.0", None),
+                (
+                    "1.0.0",
+                    ">=1.0.0-alpha.1, <1.0.0",
+                    Some(">=1.0.0-alpha.1, <1.0.1"),
+                ),
+                (
+                    "1.0.0-alpha.1",
+                    ">=1.0.0-beta.1, <2",
+                    Some(">=1.0.0-alpha.1, <2"),
+                ),
+                (
+                    "1.0.0-rc.1",
+                    ">=0.9, <=1.0.0-beta.1",
+                    Some(">=0.9, <=1.0.0-rc.1"),
+                ),
+            ];
+            for (version, req, expected) in cases {
+                let parsed = semver::Version::parse(version).unwrap();
+                let actual = upgrade_requirement(req, &parsed).unwrap();
+                assert_eq!(actual.as_deref(), expected, "`{req}` with {version}");
+            }
+        }
+
+        #[test]
+        fn bounds_unmatched_prerelease() {
+            let version = semver::Version::parse("0.17.0-rc.1").unwrap();
+            assert!(upgrade_requirement(">=0.14, <0.17", &version).is_err());
+        }
     }
 }
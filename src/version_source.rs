@@ -0,0 +1,342 @@
+//! Where the versions of crates are looked up.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use url::Url;
+
+use super::cache::LookupCache;
+use super::errors::*;
+use super::fetch::suggest_crate_names;
+use super::local_source::LocalSource;
+use super::VersionExt;
+
+/// A version of a crate, as listed by a [`VersionSource`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrateVersion {
+    /// Name of the crate, as spelled by the source
+    pub name: String,
+    /// The version itself
+    pub version: semver::Version,
+    /// Whether the version was yanked
+    pub yanked: bool,
+    /// Features of the version, including those of its optional dependencies
+    pub available_features: BTreeMap<String, Vec<String>>,
+    /// Minimal Rust version the version supports, e.g. `1.65`, if it declares one
+    ///
+    /// Git indexes like crates.io's never fill this in, so the versions they list all pass
+    /// [`CrateVersion::supports_rust`].  Only local registries report it, see
+    /// [`VersionSource::reports_rust_version`].
+    pub rust_version: Option<String>,
+}
+
+impl CrateVersion {
+    /// Whether `rust` is at least the version's `rust-version`
+    ///
+    /// Versions that don't declare one, or declare one that doesn't parse, are assumed to
+    /// support any Rust.
+    pub fn supports_rust(&self, rust: &semver::Version) -> bool {
+        let rust_version = match &self.rust_version {
+            Some(rust_version) => rust_version,
+            None => return true,
+        };
+        let mut parts = rust_version.trim().split('.').map(str::parse::<u64>);
+        let required = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), minor, patch, None) => {
+                let minor = minor.unwrap_or(Ok(0));
+                let patch = patch.unwrap_or(Ok(0));
+                match (minor, patch) {
+                    (Ok(minor), Ok(patch)) => (major, minor, patch),
+                    _ => return true,
+                }
+            }
+            _ => return true,
+        };
+        (rust.major, rust.minor, rust.patch) >= required
+    }
+}
+
+/// Versions of crates and their features, e.g. from a registry index
+///
+/// See [`registry_source`] for the sources cargo-edit reads by default.  Only
+/// [`VersionSource::versions`] has to be implemented, the other queries are answered from it.
+pub trait VersionSource {
+    /// All versions of `name`, which is empty if the source doesn't have the crate
+    fn versions(&self, name: &str) -> CargoResult<Vec<CrateVersion>>;
+
+    /// Highest version of `name` that matches `req` and isn't yanked
+    ///
+    /// Pre-releases are only considered with `allow_prerelease`, and then only match `req` as
+    /// described by [`semver::VersionReq::matches`].
+    fn latest_matching(
+        &self,
+        name: &str,
+        req: Option<&semver::VersionReq>,
+        allow_prerelease: bool,
+    ) -> CargoResult<Option<CrateVersion>> {
+        self.find_latest_version(name, req, &|_| true, allow_prerelease)
+    }
+
+    /// Whether the versions listed have a [`CrateVersion::rust_version`] when they declare one
+    ///
+    /// When they don't, filtering by [`CrateVersion::supports_rust`] lets all of them through.
+    fn reports_rust_version(&self) -> bool {
+        true
+    }
+
+    /// Like [`VersionSource::latest_matching`], only considering versions `filter` accepts
+    ///
+    /// E.g. pass [`CrateVersion::supports_rust`] to skip versions needing a newer Rust, after
+    /// checking [`VersionSource::reports_rust_version`].
+    fn find_latest_version(
+        &self,
+        name: &str,
+        req: Option<&semver::VersionReq>,
+        filter: &dyn Fn(&CrateVersion) -> bool,
+        allow_prerelease: bool,
+    ) -> CargoResult<Option<CrateVersion>> {
+        let versions = self.versions(name)?;
+        Ok(latest_matching(&versions, req, filter, allow_prerelease).cloned())
+    }
+
+    /// Features of `version` of `name`, if the source has that version
+    fn features_of(
+        &self,
+        name: &str,
+        version: &semver::Version,
+    ) -> CargoResult<Option<BTreeMap<String, Vec<String>>>> {
+        let features = self
+            .versions(name)?
+            .into_iter()
+            .find(|v| v.version == *version)
+            .map(|v| v.available_features);
+        Ok(features)
+    }
+
+    /// Error for when no spelling of `name` is in the source
+    fn not_found(&self, name: &str) -> Error {
+        no_crate_err(name, &suggest_crate_names(self, name))
+    }
+}
+
+/// Highest version in `versions` that matches `req`, passes `filter` and isn't yanked
+pub(crate) fn latest_matching<'v>(
+    versions: &'v [CrateVersion],
+    req: Option<&semver::VersionReq>,
+    filter: &dyn Fn(&CrateVersion) -> bool,
+    allow_prerelease: bool,
+) -> Option<&'v CrateVersion> {
+    versions
+        .iter()
+        .filter(|&v| allow_prerelease || !v.version.is_prerelease())
+        .filter(|&v| req.map(|req| req.matches(&v.version)).unwrap_or(true))
+        .filter(|&v| !v.yanked)
+        .filter(|&v| filter(v))
+        .max_by_key(|&v| v.version.clone())
+}
+
+/// The source for `registry`, either a registry index or a local source like `cargo vendor`'s
+///
+/// Versions looked up in a remote registry are cached on disk for a few minutes, shared by all
+/// commands.
+pub fn registry_source(registry: &Url) -> CargoResult<Box<dyn VersionSource>> {
+    registry_source_with(registry, false)
+}
+
+/// Like [`registry_source`], with `refresh` looking versions up again instead of the cached ones
+///
+/// The cache is still updated with what is looked up.
+pub fn registry_source_with(registry: &Url, refresh: bool) -> CargoResult<Box<dyn VersionSource>> {
+    if let Some(source) = LocalSource::from_url(registry) {
+        return Ok(Box::new(source));
+    }
+    let index = crates_index::Index::from_url(registry.as_str())
+        .with_kind(ErrorKind::RegistryUnavailable)?;
+    let cache = LookupCache::for_registry(registry);
+    Ok(Box::new(RegistryIndex::new(index, cache, refresh)))
+}
+
+/// A git registry index, like crates.io's
+///
+/// Only the entries of the crates asked for are read from the checkout, each of them once.
+/// Versions never have a [`CrateVersion::rust_version`], which `crates-index` doesn't expose, so
+/// filtering them by the Rust they support lets all of them through.
+struct RegistryIndex {
+    index: crates_index::Index,
+    /// Entries read so far, empty for the crates the index doesn't have
+    entries: RefCell<BTreeMap<String, Vec<CrateVersion>>>,
+    /// Entries read by recent commands
+    cache: Option<LookupCache>,
+    /// Whether to read entries again rather than take them from `cache`
+    refresh: bool,
+}
+
+impl RegistryIndex {
+    fn new(index: crates_index::Index, cache: Option<LookupCache>, refresh: bool) -> Self {
+        Self {
+            index,
+            entries: Default::default(),
+            cache,
+            refresh,
+        }
+    }
+
+    /// Run `f` on the versions of `name`, reading its entry if it wasn't yet
+    fn with_versions<R>(&self, name: &str, f: impl FnOnce(&[CrateVersion]) -> R) -> CargoResult<R> {
+        if let Some(versions) = self.entries.borrow().get(name) {
+            return Ok(f(versions));
+        }
+
+        let cached = self
+            .cache
+            .as_ref()
+            .filter(|_| !self.refresh)
+            .and_then(|cache| cache.get(name));
+        if let Some(versions) = cached {
+            let result = f(&versions);
+            self.entries.borrow_mut().insert(name.to_owned(), versions);
+            return Ok(result);
+        }
+
+        trace!("reading the index entry of `{name}`");
+        let versions = match self.index.crate_(name) {
+            Some(crate_) => crate_
+                .versions()
+                .iter()
+                .map(|v| {
+                    Ok(CrateVersion {
+                        name: v.name().to_owned(),
+                        version: v.version().parse()?,
+                        yanked: v.is_yanked(),
+                        available_features: registry_features(v),
+                        // Not exposed by this version of `crates-index`
+                        rust_version: None,
+                    })
+                })
+                .collect::<CargoResult<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        // Names the index doesn't have are mostly typos and probes for suggestions, which
+        // would only fill the cache
+        if let Some(cache) = self.cache.as_ref().filter(|_| !versions.is_empty()) {
+            cache.insert(name, &versions);
+        }
+        let result = f(&versions);
+        self.entries.borrow_mut().insert(name.to_owned(), versions);
+        Ok(result)
+    }
+}
+
+impl VersionSource for RegistryIndex {
+    fn versions(&self, name: &str) -> CargoResult<Vec<CrateVersion>> {
+        self.with_versions(name, |versions| versions.to_vec())
+    }
+
+    fn reports_rust_version(&self) -> bool {
+        false
+    }
+
+    fn find_latest_version(
+        &self,
+        name: &str,
+        req: Option<&semver::VersionReq>,
+        filter: &dyn Fn(&CrateVersion) -> bool,
+        allow_prerelease: bool,
+    ) -> CargoResult<Option<CrateVersion>> {
+        self.with_versions(name, |versions| {
+            latest_matching(versions, req, filter, allow_prerelease).cloned()
+        })
+    }
+}
+
+impl VersionSource for LocalSource {
+    fn versions(&self, name: &str) -> CargoResult<Vec<CrateVersion>> {
+        self.query(name)
+    }
+
+    fn not_found(&self, name: &str) -> Error {
+        no_vendored_crate_err(name, self.path().display())
+    }
+}
+
+fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>> {
+    let mut features: BTreeMap<_, _> = v
+        .features()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    features.extend(
+        v.dependencies()
+            .iter()
+            .filter(|d| d.is_optional())
+            .map(|d| (d.crate_name().to_owned(), vec![])),
+    );
+    features
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn crate_version(version: &str, rust_version: Option<&str>, yanked: bool) -> CrateVersion {
+        CrateVersion {
+            name: "foo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked,
+            available_features: BTreeMap::new(),
+            rust_version: rust_version.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn supports_rust() {
+        let rust = semver::Version::new(1, 65, 0);
+        assert!(crate_version("1.0.0", None, false).supports_rust(&rust));
+        assert!(crate_version("1.0.0", Some("1.65"), false).supports_rust(&rust));
+        assert!(crate_version("1.0.0", Some("1.60.1"), false).supports_rust(&rust));
+        assert!(!crate_version("1.0.0", Some("1.65.1"), false).supports_rust(&rust));
+        assert!(!crate_version("1.0.0", Some("1.70"), false).supports_rust(&rust));
+        assert!(!crate_version("1.0.0", Some("2"), false).supports_rust(&rust));
+        assert!(crate_version("1.0.0", Some("nightly"), false).supports_rust(&rust));
+    }
+
+    #[test]
+    fn latest_with_filter() {
+        let versions = [
+            crate_version("1.1.0", Some("1.70"), false),
+            crate_version("1.0.2", Some("1.60"), true),
+            crate_version("1.0.1", Some("1.60"), false),
+            crate_version("1.0.0", None, false),
+            crate_version("1.2.0-rc.1", None, false),
+        ];
+        let rust = semver::Version::new(1, 65, 0);
+
+        let msrv = |v: &CrateVersion| v.supports_rust(&rust);
+        let latest = latest_matching(&versions, None, &msrv, false);
+        assert_eq!(
+            latest.map(|v| v.version.to_string()).as_deref(),
+            Some("1.0.1")
+        );
+
+        let latest = latest_matching(&versions, None, &msrv, true);
+        assert_eq!(
+            latest.map(|v| v.version.to_string()).as_deref(),
+            Some("1.2.0-rc.1")
+        );
+
+        let req = semver::VersionReq::parse("=1.0.0").unwrap();
+        let latest = latest_matching(&versions, Some(&req), &msrv, false);
+        assert_eq!(
+            latest.map(|v| v.version.to_string()).as_deref(),
+            Some("1.0.0")
+        );
+
+        let latest = latest_matching(&versions, None, &|v| v.rust_version.is_none(), false);
+        assert_eq!(
+            latest.map(|v| v.version.to_string()).as_deref(),
+            Some("1.0.0")
+        );
+
+        let latest = latest_matching(&versions, None, &|_| false, true);
+        assert_eq!(latest, None);
+    }
+}
@@ -0,0 +1,3 @@
+use once_cell::sync::Lazy;
+
+static DOCOPT: Lazy<u8> = Lazy::new(|| 0);
@@ -17,7 +17,7 @@ fn case() {
         .args(["--build", "semver"])
         .current_dir(cwd)
         .assert()
-        .code(1)
+        .code(10)
         .stdout_matches_path(curr_dir!().join("stdout.log"))
         .stderr_matches_path(curr_dir!().join("stderr.log"));
 
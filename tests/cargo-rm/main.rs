@@ -7,23 +7,50 @@ extern crate cargo_test_macro;
 
 mod dry_run;
 mod invalid_arg;
+mod invalid_check_usage;
 mod invalid_dep;
+mod invalid_dep_json;
+mod invalid_pkgid_spec;
+mod invalid_rm_feature;
+mod invalid_rm_optional_feature;
+mod invalid_rm_renamed;
 mod invalid_rm_target;
 mod invalid_rm_target_dep;
+mod invalid_rm_target_other;
 mod invalid_section;
 mod invalid_section_dep;
+mod invalid_update_lockfile;
+mod invalid_workspace_dep;
 mod no_arg;
+mod rm_all_sections;
 mod rm_avoid_empty_tables;
 mod rm_build;
+mod rm_check_usage;
 mod rm_dev;
+mod rm_dotted;
 mod rm_existing;
+mod rm_feature_references;
+mod rm_features;
+mod rm_keep_patches;
+mod rm_message_format_json;
 mod rm_multiple_deps;
 mod rm_multiple_dev;
 mod rm_optional_dep_feature;
 mod rm_optional_feature;
+mod rm_pkgid_spec;
+mod rm_prune_patches;
+mod rm_prune_patches_overrides_keep;
+mod rm_renamed;
 mod rm_target;
 mod rm_target_build;
+mod rm_target_cfg;
 mod rm_target_dev;
+mod rm_target_triple_cfg;
+mod rm_tidy_layout;
+mod rm_unused;
+mod rm_update_lockfile;
+mod rm_workspace;
+mod rm_workspace_dep;
 
 fn init_registry() {
     cargo_test_support::registry::init();
@@ -121,5 +148,10 @@ impl CargoCommand for snapbox::cmd::Command {
         Self::new(cargo_exe())
             .with_assert(cargo_test_support::compare::assert_ui())
             .test_env()
+            // Keep lookups of the test registries out of the user's cache
+            .env(
+                "CARGO_EDIT_CACHE_DIR",
+                cargo_test_support::paths::home().join(".cache/cargo-edit"),
+            )
     }
 }
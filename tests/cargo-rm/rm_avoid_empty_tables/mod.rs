@@ -14,7 +14,7 @@ fn case() {
 
     snapbox::cmd::Command::cargo_ui()
         .arg("rm")
-        .args(["clippy"])
+        .args(["clippy", "--force"])
         .current_dir(cwd)
         .assert()
         .success()
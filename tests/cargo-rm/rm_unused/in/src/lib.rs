@@ -0,0 +1 @@
+use my_package as _;
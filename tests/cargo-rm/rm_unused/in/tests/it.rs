@@ -0,0 +1 @@
+use my_dev_package1 as _;
@@ -17,7 +17,7 @@ fn case() {
         .args(["0.0.1"])
         .current_dir(cwd)
         .assert()
-        .code(1)
+        .code(9)
         .stdout_matches_path(curr_dir!().join("stdout.log"))
         .stderr_matches_path(curr_dir!().join("stderr.log"));
 
@@ -8,12 +8,17 @@ extern crate cargo_test_macro;
 mod downgrade_error;
 mod dry_run;
 mod ignore_dependent;
+mod locked_stale_lockfile;
+mod preserves_formatting;
 mod relative_absolute_conflict;
+mod select_glob;
+mod select_glob_unmatched;
 mod set_absolute_version;
 mod set_absolute_workspace_version;
 mod set_relative_version;
 mod set_relative_workspace_version;
 mod upgrade_compatible_dependency;
+mod upgrade_dependent_tables;
 mod upgrade_incompatible_dependency;
 mod upgrade_workspace;
 mod virtual_workspace_deps;
@@ -117,5 +122,10 @@ impl CargoCommand for snapbox::cmd::Command {
         Self::new(cargo_exe())
             .with_assert(cargo_test_support::compare::assert_ui())
             .test_env()
+            // Keep lookups of the test registries out of the user's cache
+            .env(
+                "CARGO_EDIT_CACHE_DIR",
+                cargo_test_support::paths::home().join(".cache/cargo-edit"),
+            )
     }
 }
@@ -16,7 +16,7 @@ fn case() {
         .arg("upgrade")
         .current_dir(cwd)
         .assert()
-        .code(1)
+        .code(5)
         .stdout_matches_path(curr_dir!().join("stdout.log"))
         .stderr_matches_path(curr_dir!().join("stderr.log"));
 
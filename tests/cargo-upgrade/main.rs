@@ -6,9 +6,11 @@
 extern crate cargo_test_macro;
 
 mod alt_registry;
+mod config;
 mod dry_run;
 mod exclude_dep;
 mod exclude_renamed;
+mod frozen;
 mod implicit_prerelease;
 mod invalid_dep;
 mod invalid_flag;
@@ -22,6 +24,8 @@ mod preserve_op;
 mod preserve_precision_major;
 mod preserve_precision_minor;
 mod preserve_precision_patch;
+mod preserves_dotted_table;
+mod preserves_formatting;
 mod preserves_inline_table;
 mod preserves_std_table;
 mod single_dep;
@@ -230,5 +234,10 @@ impl CargoCommand for snapbox::cmd::Command {
         Self::new(cargo_exe())
             .with_assert(cargo_test_support::compare::assert_ui())
             .test_env()
+            // Keep lookups of the test registries out of the user's cache
+            .env(
+                "CARGO_EDIT_CACHE_DIR",
+                cargo_test_support::paths::home().join(".cache/cargo-edit"),
+            )
     }
 }
@@ -1,5 +1,9 @@
 #[test]
 fn cli_tests() {
     let t = trycmd::TestCases::new();
+    t.env(
+        "CARGO_EDIT_CACHE_DIR",
+        concat!(env!("CARGO_TARGET_TMPDIR"), "/cargo-edit-cache"),
+    );
     t.case("README.md");
 }